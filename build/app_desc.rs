@@ -0,0 +1,77 @@
+//! Build-time inputs of the `esp_app_desc!` macro.
+
+use std::env;
+
+use anyhow::{Context, Result};
+use embuild::{build, cargo};
+
+/// The environment variable with the deterministic build timestamp, as per
+/// <https://reproducible-builds.org/specs/source-date-epoch/>
+const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// Pass the `SOURCE_DATE_EPOCH` date and time (if set) to the `esp_app_desc!` macro, and warn
+/// if `CONFIG_APP_REPRODUCIBLE_BUILD` is enabled but the Rust code is not built reproducibly.
+pub fn configure(cfg_args: &build::CfgArgs) -> Result<()> {
+    cargo::track_env_var(SOURCE_DATE_EPOCH);
+
+    if let Some(epoch) = env::var(SOURCE_DATE_EPOCH)
+        .ok()
+        .filter(|epoch| !epoch.is_empty())
+    {
+        let epoch = epoch
+            .trim()
+            .parse::<u64>()
+            .with_context(|| format!("{SOURCE_DATE_EPOCH}='{epoch}' is not a UNIX timestamp"))?;
+
+        let (date, time) = utc_date_time(epoch);
+
+        println!("cargo:rustc-env=ESP_IDF_SYS_SOURCE_DATE={date}");
+        println!("cargo:rustc-env=ESP_IDF_SYS_SOURCE_TIME={time}");
+    }
+
+    if cfg_args.get("esp_idf_app_reproducible_build").is_some() {
+        // Unlike the ESP-IDF C build, rustc does not remap the source paths on its own, so
+        // panic locations, `file!()` (and thus `EspErrorWithLocation`) and the debug info
+        // would embed the - machine-specific - absolute paths of the crates
+        let rustflags = env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+
+        if !rustflags
+            .split('\x1f')
+            .any(|flag| flag.starts_with("--remap-path-prefix"))
+        {
+            cargo::print_warning(
+                "(esp-idf-sys) CONFIG_APP_REPRODUCIBLE_BUILD is enabled, but the Rust code is built \
+                 without `--remap-path-prefix` rustflags and will embed the absolute paths of its sources",
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Convert the UNIX timestamp `epoch` to the `%Y-%m-%d` UTC date and the `%H:%M:%S` UTC time.
+fn utc_date_time(epoch: u64) -> (String, String) {
+    let days = (epoch / 86400) as i64;
+    let secs = epoch % 86400;
+
+    // Days to civil date, as per http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (
+        format!("{year:04}-{month:02}-{day:02}"),
+        format!(
+            "{:02}:{:02}:{:02}",
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60
+        ),
+    )
+}
@@ -8,8 +8,13 @@ use embuild::bindgen::BindgenExt;
 use embuild::utils::OsStrExt;
 use embuild::{bindgen as bindgen_utils, build, cargo, kconfig, path_buf};
 
+mod app_desc;
 mod common;
 mod config;
+mod errors;
+mod libc_checks;
+mod patches;
+mod start;
 
 // Features `native` and `pio` control whether the build is performed using the "native" ESP IDF CMake-based build,
 // or via the PlatformIO `espressif32` module. They work as follows:
@@ -251,11 +256,19 @@ fn main() -> anyhow::Result<()> {
     // Cargo fmt generated bindings.
     bindgen_utils::cargo_fmt_file(&bindings_file);
 
+    // Catalogue the `ESP_ERR_*` codes found in the bindings by component and kind;
+    // see `EspError::component` and `EspError::kind`
+    errors::generate(
+        &bindings_file,
+        cargo::out_dir().join("esp_err_catalogue.rs"),
+    )
+    .context("failed to generate the ESP-IDF error code catalogue")?;
+
     let cfg_args = build::CfgArgs {
         args: cfg_args
             .args
             .into_iter()
-            .chain(EspIdfVersion::parse(bindings_file)?.cfg_args())
+            .chain(EspIdfVersion::parse(&bindings_file)?.cfg_args())
             .chain(build_output.components.cfg_args())
             .chain(once(mcu))
             .collect(),
@@ -263,6 +276,47 @@ fn main() -> anyhow::Result<()> {
     cfg_args.propagate();
     cfg_args.output();
 
+    // ESP-IDF v5.1 and v5.2 only have a console whose standard streams can be restored
+    // on top of its primary device; see `esp_idf_sys::stdio`
+    if [
+        "esp_idf_comp_vfs_enabled",
+        "esp_idf_vfs_support_io",
+        "esp_idf_version_at_least_5_1_0",
+    ]
+    .iter()
+    .all(|cfg| cfg_args.get(cfg).is_some())
+        && cfg_args.get("esp_idf_version_at_least_5_3_0").is_none()
+        && [
+            "esp_idf_esp_console_uart",
+            "esp_idf_esp_console_usb_serial_jtag",
+            "esp_idf_esp_console_usb_cdc",
+        ]
+        .iter()
+        .any(|cfg| cfg_args.get(cfg).is_some())
+    {
+        println!("cargo:rustc-cfg=esp_idf_sys_stdio_legacy_console");
+    }
+
+    // Compile in the patches to the functions the ESP-IDF being built lacks
+    let optional_patches = patches::configure_optional(&build_output.esp_idf)
+        .context("failed to look up the functions to patch in the ESP-IDF sources")?;
+
+    // Check the types and constants shared by the bindings and the `libc` crate;
+    // see `src/checks/libc.rs`
+    if std::env::var("CARGO_FEATURE_STD").is_ok() {
+        libc_checks::generate(
+            &bindings_file,
+            &cfg_args,
+            cargo::out_dir().join("libc_checks.rs"),
+        )
+        .context("failed to generate the libc compatibility checks")?;
+    }
+
+    app_desc::configure(&cfg_args)?;
+
+    start::configure(&build_output.config, &cfg_args)
+        .context("invalid binstart/libstart configuration")?;
+
     // In case other crates need to have access to the ESP-IDF C headers
     build_output.cincl_args.propagate();
 
@@ -277,7 +331,10 @@ fn main() -> anyhow::Result<()> {
         build_output.esp_idf.try_to_str()?,
     );
 
-    if let Some(link_args) = build_output.link_args {
+    if let Some(mut link_args) = build_output.link_args {
+        // Make sure the patches in `src/patches` are linked
+        patches::configure(&mut link_args, &optional_patches);
+
         link_args.propagate();
 
         // Only necessary for building the examples
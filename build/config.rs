@@ -38,6 +38,50 @@ pub struct BuildConfig {
     /// The name of the root crate currently compiling for, in the event that the
     /// workspace does not have a root crate.
     pub esp_idf_sys_root_crate: Option<String>,
+
+    /// The stack size (in bytes) of the task the Rust `main` function is spawned on.
+    pub esp_idf_sys_main_task_stack_size: Option<u32>,
+
+    /// The FreeRTOS priority of the task the Rust `main` function is spawned on.
+    pub esp_idf_sys_main_task_priority: Option<u32>,
+
+    /// The core (`0`, `1`, ... or `any`) the task the Rust `main` function is spawned
+    /// on is pinned to.
+    pub esp_idf_sys_main_task_core: Option<MainTaskCore>,
+
+    /// What happens once the Rust `main` function returns (`return`, `restart`,
+    /// `deep_sleep` or `spin`).
+    pub esp_idf_sys_main_exit: Option<String>,
+
+    /// The whitespace-separated arguments passed to the Rust `main` function.
+    pub esp_idf_sys_main_args: Option<String>,
+
+    /// The NVS namespace and key (`<namespace>/<key>`) of a string with the
+    /// whitespace-separated arguments passed to the Rust `main` function.
+    pub esp_idf_sys_main_args_nvs: Option<String>,
+}
+
+/// The core affinity of the task the Rust `main` function is spawned on.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum MainTaskCore {
+    /// A core number, from `Cargo.toml` metadata.
+    Core(u32),
+    /// A core number or `any`, from `Cargo.toml` metadata or the environment.
+    Name(String),
+}
+
+impl MainTaskCore {
+    /// Get the core number, or [`None`] if the task is not pinned to a core.
+    pub fn core(&self) -> Result<Option<u32>> {
+        match self {
+            Self::Core(core) => Ok(Some(*core)),
+            Self::Name(name) if name.trim().eq_ignore_ascii_case("any") => Ok(None),
+            Self::Name(name) => name.trim().parse().map(Some).with_context(|| {
+                anyhow!("invalid main task core '{name}', expected a core number or `any`")
+            }),
+        }
+    }
 }
 
 impl BuildConfig {
@@ -133,6 +177,12 @@ impl BuildConfig {
                     #[cfg(any(feature = "native", not(feature = "pio")))]
                         native: _,
                     esp_idf_sys_root_crate: _,
+                    esp_idf_sys_main_task_stack_size,
+                    esp_idf_sys_main_task_priority,
+                    esp_idf_sys_main_task_core,
+                    esp_idf_sys_main_exit,
+                    esp_idf_sys_main_args,
+                    esp_idf_sys_main_args_nvs,
                 },
         } = EspIdfSys::deserialize(&root_package.metadata)?;
 
@@ -147,6 +197,24 @@ impl BuildConfig {
             esp_idf_tools_install_dir,
         );
         utils::set_when_none(&mut self.mcu, mcu);
+        utils::set_when_none(
+            &mut self.esp_idf_sys_main_task_stack_size,
+            esp_idf_sys_main_task_stack_size,
+        );
+        utils::set_when_none(
+            &mut self.esp_idf_sys_main_task_priority,
+            esp_idf_sys_main_task_priority,
+        );
+        utils::set_when_none(
+            &mut self.esp_idf_sys_main_task_core,
+            esp_idf_sys_main_task_core,
+        );
+        utils::set_when_none(&mut self.esp_idf_sys_main_exit, esp_idf_sys_main_exit);
+        utils::set_when_none(&mut self.esp_idf_sys_main_args, esp_idf_sys_main_args);
+        utils::set_when_none(
+            &mut self.esp_idf_sys_main_args_nvs,
+            esp_idf_sys_main_args_nvs,
+        );
 
         #[cfg(any(feature = "native", not(feature = "pio")))]
         self.native.with_cargo_metadata(root_package, &metadata)?;
@@ -0,0 +1,139 @@
+//! Generation of the `ESP_ERR_*` error code catalogue, which backs
+//! `EspError::component()` and `EspError::kind()`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+
+/// The synthetic component of the generic `ESP_ERR_*` codes (`ESP_ERR_NO_MEM`,
+/// `ESP_ERR_INVALID_ARG` etc.), which do not have an `ESP_ERR_*_BASE` of their own.
+const COMMON_COMPONENT: &str = "common";
+
+/// Error name fragments, mapped to the `EspErrorKind` variant of the errors containing them.
+///
+/// The first matching entry wins, so more specific fragments need to come first.
+const KINDS: &[(&str, &str)] = &[
+    ("TIMEOUT", "Timeout"),
+    ("NO_MEM", "NoMemory"),
+    ("NOMEM", "NoMemory"),
+    ("OUT_OF_MEM", "NoMemory"),
+    ("NOT_FOUND", "NotFound"),
+    ("NOT_EXIST", "NotFound"),
+    ("BUSY", "Busy"),
+    ("IN_PROGRESS", "Busy"),
+    ("NOT_SUPPORT", "NotSupported"),
+    ("INVALID_STATE", "InvalidState"),
+    ("NOT_INIT", "InvalidState"),
+    ("NOT_STARTED", "InvalidState"),
+    ("NOT_STOPPED", "InvalidState"),
+    ("INVALID_ARG", "InvalidArgument"),
+    ("INVALID_PARAM", "InvalidArgument"),
+    ("INVALID_SIZE", "InvalidArgument"),
+    ("INVALID_LENGTH", "InvalidArgument"),
+    ("INVALID_NAME", "InvalidArgument"),
+    ("_ARG", "InvalidArgument"),
+];
+
+/// Generate the error catalogue from the `ESP_ERR_*` constants in `bindings_file`
+/// into `output_file`.
+///
+/// Every `ESP_ERR_<COMPONENT>_BASE` constant denotes a component. Every other
+/// `ESP_ERR_*` constant is assigned to the component with the longest base name
+/// prefixing its own name, or - failing that - to the component with the closest
+/// base below its code, or - failing that too - to the [`COMMON_COMPONENT`].
+pub fn generate(bindings_file: impl AsRef<Path>, output_file: impl AsRef<Path>) -> Result<()> {
+    let bindings = fs::read_to_string(bindings_file.as_ref())?;
+
+    let consts = regex::Regex::new(r"pub\s+const\s+(ESP_ERR_\w+)\s*:\s*i32\s*=\s*(-?\d+)\s*;")?
+        .captures_iter(&bindings)
+        .filter_map(|c| Some((c.get(1)?.as_str(), c.get(2)?.as_str().parse::<i32>().ok()?)))
+        // (also de-duplicates the constants coming from the per-module bindings)
+        .collect::<BTreeMap<_, _>>();
+
+    let mut components = consts
+        .iter()
+        .filter_map(|(name, code)| {
+            name.strip_suffix("_BASE")
+                .map(|prefix| (format!("{prefix}_"), *code))
+        })
+        .collect::<Vec<_>>();
+
+    components.sort_by_key(|(prefix, base)| (*base, prefix.clone()));
+    components.insert(0, ("ESP_ERR_".into(), 0));
+
+    let component_of = |name: &str, code: i32| {
+        components
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, (prefix, _))| name.starts_with(prefix.as_str()))
+            .max_by_key(|(_, (prefix, _))| prefix.len())
+            .or_else(|| {
+                components
+                    .iter()
+                    .enumerate()
+                    .skip(1)
+                    .filter(|(_, (_, base))| *base <= code)
+                    .max_by_key(|(_, (_, base))| *base)
+            })
+            .map(|(index, _)| index)
+            .unwrap_or(0)
+    };
+
+    let kind_of = |name: &str| {
+        KINDS
+            .iter()
+            .find(|(fragment, _)| name.contains(fragment))
+            .map(|(_, kind)| *kind)
+            .unwrap_or("Other")
+    };
+
+    // Several constants might share a code; the one belonging to the most specific
+    // component (i.e. the one with the closest base) wins
+    let mut errors = BTreeMap::new();
+    for (name, code) in consts.iter().filter(|(name, _)| !name.ends_with("_BASE")) {
+        let component = component_of(name, *code);
+
+        errors
+            .entry(*code)
+            .and_modify(|entry: &mut (usize, &str)| {
+                if components[component].1 > components[entry.0].1 {
+                    *entry = (component, kind_of(name));
+                }
+            })
+            .or_insert((component, kind_of(name)));
+    }
+
+    let mut output = String::new();
+
+    writeln!(
+        output,
+        "pub(super) static COMPONENTS: &[(&str, esp_err_t)] = &["
+    )?;
+    for (index, (prefix, base)) in components.iter().enumerate() {
+        let name = if index == 0 {
+            COMMON_COMPONENT.to_owned()
+        } else {
+            prefix["ESP_ERR_".len()..prefix.len() - 1].to_lowercase()
+        };
+
+        writeln!(output, "    ({name:?}, {base}),")?;
+    }
+    writeln!(output, "];")?;
+
+    writeln!(
+        output,
+        "pub(super) static ERRORS: &[(esp_err_t, u16, EspErrorKind)] = &["
+    )?;
+    for (code, (component, kind)) in errors {
+        writeln!(output, "    ({code}, {component}, EspErrorKind::{kind}),")?;
+    }
+    writeln!(output, "];")?;
+
+    fs::write(output_file.as_ref(), output)?;
+
+    Ok(())
+}
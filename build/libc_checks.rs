@@ -0,0 +1,758 @@
+//! Generation of the compile-time compatibility checks between the bindings and the
+//! `libc` crate (see `src/checks/libc.rs`).
+//!
+//! The sources of the `libc` crate are parsed with `syn` for the public types, structures
+//! (with their fields) and integer constants active for the target - following the `mod`
+//! and `pub use` declarations from its root, evaluating `#[cfg]` attributes and `cfg_if!`
+//! branches against the `CARGO_CFG_*` variables and the features `libc` is built with -
+//! and the ones also defined by the bindings get a size, alignment, field offset or value
+//! check.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use anyhow::{anyhow, Context, Result};
+use embuild::{build, cargo};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{braced, Attribute, Expr, ExprLit, ExprUnary, Fields, Item, Lit, Meta, Token, UnOp};
+
+use crate::common::workspace_dir;
+
+/// The integer types of the `libc` constants which are checked
+const INTEGER_TYPES: &[&str] = &[
+    "c_char",
+    "c_schar",
+    "c_uchar",
+    "c_short",
+    "c_ushort",
+    "c_int",
+    "c_uint",
+    "c_long",
+    "c_ulong",
+    "c_longlong",
+    "c_ulonglong",
+    "i8",
+    "u8",
+    "i16",
+    "u16",
+    "i32",
+    "u32",
+    "i64",
+    "u64",
+    "isize",
+    "usize",
+];
+
+/// The cfgs which are in the `CARGO_CFG_*` variables when set: the ones of rustc, and the
+/// ones `libc` leaves to its users (see the `Cfg` enum of the build script of `libc`).
+///
+/// Whether the other cfgs hold is unknown, as the build script of `libc` might set them.
+const KNOWN_CFGS: &[&str] = &[
+    "debug_assertions",
+    "doc",
+    "doctest",
+    "fmt_debug",
+    "miri",
+    "overflow_checks",
+    "panic",
+    "proc_macro",
+    "relocation_model",
+    "sanitize",
+    "target_abi",
+    "target_arch",
+    "target_endian",
+    "target_env",
+    "target_family",
+    "target_feature",
+    "target_has_atomic",
+    "target_has_atomic_equal_alignment",
+    "target_has_atomic_load_store",
+    "target_os",
+    "target_pointer_width",
+    "target_thread_local",
+    "target_vendor",
+    "test",
+    "ub_checks",
+    "unix",
+    "windows",
+    "espidf_picolibc",
+    "espidf_time32",
+];
+
+/// How a `libc` item known not to match its binding is checked
+enum Mismatch {
+    /// Not at all
+    Unchecked,
+    /// Except for its alignment
+    Alignment,
+    /// Its value is only checked not to be less than the one of its binding
+    MinValue,
+}
+
+/// The known mismatch between the `libc` item `name` and its binding, if any
+fn known_mismatch(name: &str, cfg_args: &build::CfgArgs) -> Option<Mismatch> {
+    let cfg = |name: &str| cfg_args.get(name).is_some();
+    let picolibc = cfg("esp_idf_libc_picolibc");
+
+    match name {
+        // `wchar_t` is 1 byte on xtensa (esp32/s2/s3) before IDF v5.4 vs 4 bytes in libc
+        "wchar_t"
+            if (cfg("esp32") || cfg("esp32s2") || cfg("esp32s3"))
+                && !cfg("esp_idf_version_at_least_5_4_0") =>
+        {
+            Some(Mismatch::Unchecked)
+        }
+        // 32-bit handles in ESP-IDF, which `libc` aligns to 8 bytes on RISC-V and Xtensa:
+        // https://github.com/rust-lang/libc/blob/0.2.190/src/unix/newlib/mod.rs
+        "pthread_mutex_t" | "pthread_rwlock_t" | "pthread_cond_t" => Some(Mismatch::Alignment),
+        // Marked as unverified in `libc`, see the link above
+        "pthread_attr_t"
+        | "pthread_rwlockattr_t"
+        | "pthread_mutexattr_t"
+        | "pthread_condattr_t" => Some(Mismatch::Unchecked),
+        // The bindings have the value of lwIP, whose interface names are shorter than the
+        // ones `libc` has room for
+        "IF_NAMESIZE" | "IFNAMSIZ" => Some(Mismatch::MinValue),
+        // Defined by the newlib headers, but not used by ESP-IDF:
+        // https://github.com/rust-lang/libc/pull/5034
+        "DT_FIFO" | "DT_CHR" if !picolibc => Some(Mismatch::Unchecked),
+        // `libc` has the signals of newlib only:
+        // https://github.com/esp-rs/esp-idf-sys/issues/410
+        "SIGABRT" | "SIGFPE" | "SIGILL" | "SIGINT" | "SIGSEGV" | "SIGTERM" | "SIGHUP"
+        | "SIGQUIT" | "NSIG" | "sigaction" | "stack_t" | "sigval"
+            if picolibc =>
+        {
+            Some(Mismatch::Unchecked)
+        }
+        // The `termios.h` of ESP-IDF v6.0+ differs from the one of newlib
+        "speed_t" | "tcflag_t" | "termios" if cfg("esp_idf_version_at_least_6_0_0") => {
+            Some(Mismatch::Unchecked)
+        }
+        // The `fd_set` of lwIP, before ESP-IDF v5.0
+        "fd_set" if !cfg("esp_idf_version_at_least_5_0_0") => Some(Mismatch::Unchecked),
+        _ => None,
+    }
+}
+
+/// Generate the checks of the items defined both by `bindings_file` and by the `libc`
+/// crate into `output_file`, as an array of `Check`s.
+///
+/// Should the sources of the `libc` crate not be found or not be understood, the build
+/// goes on with a warning, and with the manual checks of `src/checks/libc.rs` only.
+pub fn generate(
+    bindings_file: impl AsRef<Path>,
+    cfg_args: &build::CfgArgs,
+    output_file: impl AsRef<Path>,
+) -> Result<()> {
+    if cfg_args.get("esp_idf_libc_picolibc").is_some()
+        && env::var_os("CARGO_CFG_ESPIDF_PICOLIBC").is_none()
+    {
+        cargo::print_warning(
+            "(esp-idf-sys) ESP-IDF is built with picolibc: add `--cfg espidf_picolibc` to the rustflags, for `libc` to have the `O_*` flags of picolibc",
+        );
+    }
+
+    let checks = checks(bindings_file.as_ref(), cfg_args).unwrap_or_else(|err| {
+        cargo::print_warning(format_args!(
+            "(esp-idf-sys) Only some of the libc compatibility checks are done: {err:#}"
+        ));
+
+        "[]".into()
+    });
+
+    fs::write(output_file, checks)?;
+
+    Ok(())
+}
+
+/// The checks of the items defined both by `bindings_file` and by the `libc` crate
+fn checks(bindings_file: &Path, cfg_args: &build::CfgArgs) -> Result<String> {
+    let (libc_lib, features) = libc_lib().context("failed to locate the sources of `libc`")?;
+    let libc = parse_crate(&libc_lib, &Cfgs { features }).with_context(|| {
+        anyhow!(
+            "failed to parse the sources of `libc` in '{}'",
+            libc_lib.display()
+        )
+    })?;
+
+    let bindings = {
+        let file = syn::parse_file(&fs::read_to_string(bindings_file)?)
+            .with_context(|| anyhow!("failed to parse '{}'", bindings_file.display()))?;
+
+        let cfgs = Cfgs::default();
+        let mut parser = Parser::new(&cfgs);
+        parser.parse(&file.items, Some(true))?;
+        parser.items
+    };
+
+    let unchecked = libc
+        .unknown
+        .iter()
+        .filter(|name| !libc.types.contains_key(*name) && !libc.consts.contains_key(*name))
+        .filter(|name| {
+            // A structure field is recorded as `<structure>.<field>`
+            let item = name.split('.').next().unwrap_or_default();
+
+            bindings.types.contains_key(item) || bindings.consts.contains_key(item)
+        })
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+
+    if !unchecked.is_empty() {
+        cargo::print_warning(format_args!(
+            "(esp-idf-sys) The libc compatibility of {} is not checked, as it depends on cfgs the build script of `libc` might set",
+            unchecked.join(", ")
+        ));
+    }
+
+    let mut output = String::from("[\n");
+
+    for (name, libc_item) in &libc.types {
+        let Some(bindings_item) = bindings.types.get(name) else {
+            continue;
+        };
+
+        let mismatch = known_mismatch(name, cfg_args);
+        if matches!(mismatch, Some(Mismatch::Unchecked)) {
+            continue;
+        }
+
+        writeln!(
+            &mut output,
+            "    Check::size(\"{name}\", size_of::<sys::{name}>(), size_of::<libc::{name}>()),"
+        )?;
+
+        if !matches!(mismatch, Some(Mismatch::Alignment)) {
+            writeln!(
+                &mut output,
+                "    Check::align(\"{name}\", align_of::<sys::{name}>(), align_of::<libc::{name}>()),"
+            )?;
+        }
+
+        if let (Type::Record(libc_fields), Type::Record(bindings_fields)) =
+            (libc_item, bindings_item)
+        {
+            for field in libc_fields.iter().filter(|f| bindings_fields.contains(f)) {
+                writeln!(
+                    &mut output,
+                    "    Check::offset(\"{name}\", \"{field}\", \
+                         offset_of!(sys::{name}, {field}), offset_of!(libc::{name}, {field})),"
+                )?;
+            }
+        }
+    }
+
+    for (name, constant) in &libc.consts {
+        // The values of the bindings constants are literals, while the ones of the `libc`
+        // constants might be expressions, so only their types tell whether they are integers
+        if !bindings
+            .consts
+            .get(name)
+            .is_some_and(|constant| is_integer(&constant.value))
+            || !libc.is_integer_type(&constant.ty)
+        {
+            continue;
+        }
+
+        let check = match known_mismatch(name, cfg_args) {
+            Some(Mismatch::Unchecked) => continue,
+            Some(Mismatch::MinValue) => "min_value",
+            _ => "value",
+        };
+
+        writeln!(
+            &mut output,
+            "    Check::{check}(\"{name}\", sys::{name} as i64, libc::{name} as i64),"
+        )?;
+    }
+
+    output.push(']');
+
+    Ok(output)
+}
+
+/// The root source file of the `libc` crate `esp-idf-sys` depends on - wherever Cargo
+/// takes it from (registry, vendored sources, `[patch]` section...) - and the features
+/// `libc` is built with, as per `cargo metadata`
+fn libc_lib() -> Result<(PathBuf, Vec<String>)> {
+    // The `libc` version might change without any other change to `esp-idf-sys`
+    cargo::track_file(workspace_dir()?.join("Cargo.lock"));
+
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .current_dir(workspace_dir()?)
+        .other_options(vec![
+            "--offline".into(),
+            format!("--filter-platform={}", env::var("TARGET")?),
+        ])
+        .exec()?;
+
+    let name = env::var("CARGO_PKG_NAME")?;
+    let version = env::var("CARGO_PKG_VERSION")?;
+
+    let package = metadata
+        .packages
+        .iter()
+        .find(|package| package.name == name && package.version.to_string() == version)
+        .ok_or_else(|| anyhow!("`{name} {version}` is not in the metadata of the workspace"))?;
+
+    let node = |id| {
+        metadata
+            .resolve
+            .as_ref()
+            .and_then(|resolve| resolve.nodes.iter().find(|node| &node.id == id))
+    };
+
+    let libc = node(&package.id)
+        .and_then(|node| node.deps.iter().find(|dep| dep.name == "libc"))
+        .map(|dep| &metadata[&dep.pkg])
+        .ok_or_else(|| anyhow!("the `libc` dependency of `{name}` was not resolved"))?;
+
+    let lib = libc
+        .targets
+        .iter()
+        .find(|target| target.kind.iter().any(|kind| kind == "lib"))
+        .ok_or_else(|| anyhow!("`libc {}` has no library", libc.version))?;
+
+    let features = node(&libc.id)
+        .map(|node| node.features.clone())
+        .unwrap_or_default();
+
+    Ok((lib.src_path.clone().into_std_path_buf(), features))
+}
+
+/// A type defined by the `libc` crate or the bindings
+enum Type {
+    /// A type alias, with the aliased type
+    Alias(syn::Type),
+    /// A structure or union, with the names of its public fields
+    Record(Vec<String>),
+    /// A tuple structure
+    Opaque,
+}
+
+/// The public types and constants of a crate
+#[derive(Default)]
+struct Items {
+    types: BTreeMap<String, Type>,
+    consts: BTreeMap<String, Constant>,
+    /// The items - and the structure fields, as `<structure>.<field>` - which are public
+    /// or not depending on the cfgs the build script of `libc` might set
+    unknown: BTreeSet<String>,
+}
+
+/// A constant defined by the `libc` crate or the bindings
+struct Constant {
+    ty: syn::Type,
+    value: Expr,
+}
+
+impl Items {
+    /// Whether `ty` is - or is an alias of - an integer type
+    fn is_integer_type(&self, ty: &syn::Type) -> bool {
+        let mut ty = ty;
+
+        // (bounded, in case of alias cycles)
+        for _ in 0..8 {
+            let Some(name) = type_name(ty) else {
+                return false;
+            };
+
+            if INTEGER_TYPES.contains(&name.as_str()) {
+                return true;
+            }
+
+            match self.types.get(&name) {
+                Some(Type::Alias(aliased)) => ty = aliased,
+                _ => return false,
+            }
+        }
+
+        false
+    }
+}
+
+/// The evaluation of `#[cfg]` predicates, as `Some(holds)` or - should the predicate
+/// depend on a cfg the build script of `libc` might set - `None`
+#[derive(Default)]
+struct Cfgs {
+    /// The enabled features
+    features: Vec<String>,
+}
+
+impl Cfgs {
+    /// Whether the `#[cfg]` attributes among `attrs` hold
+    fn eval_attrs(&self, attrs: &[Attribute]) -> Option<bool> {
+        all(attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .map(|attr| self.eval(&attr.parse_args().ok()?)))
+    }
+
+    /// Whether `predicate` holds
+    fn eval(&self, predicate: &Meta) -> Option<bool> {
+        match predicate {
+            Meta::Path(path) => self.eval_option(&path.get_ident()?.to_string(), None),
+            Meta::NameValue(option) => match &option.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(value),
+                    ..
+                }) => self.eval_option(&option.path.get_ident()?.to_string(), Some(&value.value())),
+                _ => None,
+            },
+            Meta::List(list) => {
+                let predicates = list
+                    .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                    .ok()?;
+                let holds = predicates.iter().map(|predicate| self.eval(predicate));
+
+                if list.path.is_ident("all") {
+                    all(holds)
+                } else if list.path.is_ident("any") {
+                    any(holds)
+                } else if list.path.is_ident("not") && predicates.len() == 1 {
+                    not(all(holds))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Whether the cfg `name` - or `name = "value"` - holds
+    fn eval_option(&self, name: &str, value: Option<&str>) -> Option<bool> {
+        if name == "feature" {
+            return Some(value.is_some_and(|value| self.features.iter().any(|f| f == value)));
+        }
+
+        match env::var(format!("CARGO_CFG_{}", name.to_uppercase())) {
+            Ok(values) => Some(value.is_none_or(|value| values.split(',').any(|v| v == value))),
+            Err(_) if KNOWN_CFGS.contains(&name) => Some(false),
+            Err(_) => None,
+        }
+    }
+}
+
+/// The conjunction of `holds`, unknown if one of them is and none is false
+fn all(holds: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    let mut all = Some(true);
+
+    for holds in holds {
+        match holds {
+            Some(false) => return Some(false),
+            None => all = None,
+            Some(true) => (),
+        }
+    }
+
+    all
+}
+
+/// The disjunction of `holds`, unknown if one of them is and none is true
+fn any(holds: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    not(all(holds.into_iter().map(not)))
+}
+
+fn not(holds: Option<bool>) -> Option<bool> {
+    holds.map(|holds| !holds)
+}
+
+/// Parse the crate rooted at `lib`.
+fn parse_crate(lib: &Path, cfgs: &Cfgs) -> Result<Items> {
+    let mut parser = Parser::new(cfgs);
+    let mut files = vec![(lib.to_path_buf(), Some(true))];
+
+    while let Some((file, holds)) = files.pop() {
+        let source = fs::read_to_string(&file)
+            .with_context(|| format!("failed to read `{}`", file.display()))?;
+        let source = syn::parse_file(&source)
+            .with_context(|| format!("failed to parse `{}`", file.display()))?;
+
+        parser.mods.clear();
+        parser.glob_uses.clear();
+        parser.parse(&source.items, all([holds, cfgs.eval_attrs(&source.attrs)]))?;
+
+        // The crate root and `mod.rs` declare the modules of their directory, `name.rs` the
+        // ones of the `name` directory
+        let dir = match file.file_stem().and_then(|stem| stem.to_str()) {
+            Some(_) if file == lib => file.parent().map(Path::to_path_buf),
+            Some("mod") => file.parent().map(Path::to_path_buf),
+            Some(stem) => file.parent().map(|parent| parent.join(stem)),
+            None => None,
+        }
+        .unwrap_or_default();
+
+        // Only the modules whose items are re-exported contribute to the API
+        for (module, declared) in &parser.mods {
+            for (_, used) in parser.glob_uses.iter().filter(|(used, _)| used == module) {
+                let holds = all([*declared, *used]);
+                if holds == Some(false) {
+                    continue;
+                }
+
+                let file = dir.join(format!("{module}.rs"));
+
+                files.push((
+                    if file.exists() {
+                        file
+                    } else {
+                        dir.join(module).join("mod.rs")
+                    },
+                    holds,
+                ));
+            }
+        }
+    }
+
+    Ok(parser.items)
+}
+
+/// A parser of the public items of a crate, one source file at a time
+struct Parser<'a> {
+    cfgs: &'a Cfgs,
+    items: Items,
+    /// The modules declared by the file, with whether their declaration holds
+    mods: Vec<(String, Option<bool>)>,
+    /// The modules re-exported by a `pub use <path>::<module>::*` of the file, with whether
+    /// the re-export holds
+    glob_uses: Vec<(String, Option<bool>)>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(cfgs: &'a Cfgs) -> Self {
+        Self {
+            cfgs,
+            items: Items::default(),
+            mods: Vec::new(),
+            glob_uses: Vec::new(),
+        }
+    }
+
+    /// Parse `items`, `holds` telling whether the `cfg_if!` branch or macro invocation they
+    /// are in holds.
+    fn parse(&mut self, items: &[Item], holds: Option<bool>) -> Result<()> {
+        for item in items {
+            match item {
+                Item::Type(item)
+                    if self.is_public(item.ident.to_string(), &item.vis, &item.attrs, holds) =>
+                {
+                    self.items
+                        .types
+                        .insert(item.ident.to_string(), Type::Alias((*item.ty).clone()));
+                }
+                Item::Const(item)
+                    if self.is_public(item.ident.to_string(), &item.vis, &item.attrs, holds) =>
+                {
+                    self.items.consts.insert(
+                        item.ident.to_string(),
+                        Constant {
+                            ty: (*item.ty).clone(),
+                            value: (*item.expr).clone(),
+                        },
+                    );
+                }
+                // Generic structures are not checked
+                Item::Struct(item) if item.generics.params.is_empty() => {
+                    let name = item.ident.to_string();
+
+                    let ty = match &item.fields {
+                        Fields::Named(fields) => {
+                            Type::Record(self.fields(&name, &fields.named, holds))
+                        }
+                        Fields::Unnamed(_) => Type::Opaque,
+                        Fields::Unit => continue,
+                    };
+
+                    if self.is_public(name.clone(), &item.vis, &item.attrs, holds) {
+                        self.items.types.insert(name, ty);
+                    }
+                }
+                Item::Union(item) if item.generics.params.is_empty() => {
+                    let name = item.ident.to_string();
+                    let ty = Type::Record(self.fields(&name, &item.fields.named, holds));
+
+                    if self.is_public(name.clone(), &item.vis, &item.attrs, holds) {
+                        self.items.types.insert(name, ty);
+                    }
+                }
+                Item::Mod(item) if item.content.is_none() => {
+                    let holds = all([holds, self.cfgs.eval_attrs(&item.attrs)]);
+                    if holds != Some(false) {
+                        self.mods.push((item.ident.to_string(), holds));
+                    }
+                }
+                Item::Use(item) if matches!(item.vis, syn::Visibility::Public(_)) => {
+                    let holds = all([holds, self.cfgs.eval_attrs(&item.attrs)]);
+                    if holds != Some(false) {
+                        let mut modules = Vec::new();
+                        glob_modules(&item.tree, None, &mut modules);
+
+                        self.glob_uses
+                            .extend(modules.into_iter().map(|module| (module, holds)));
+                    }
+                }
+                Item::Macro(item) if item.ident.is_none() => {
+                    let holds = all([holds, self.cfgs.eval_attrs(&item.attrs)]);
+                    if holds == Some(false) {
+                        continue;
+                    }
+
+                    if item.mac.path.is_ident("cfg_if") {
+                        // The items of the first branch whose predicate holds
+                        let CfgIf(branches) = item.mac.parse_body()?;
+                        let mut taken = Some(false);
+
+                        for (predicate, items) in &branches {
+                            let predicate = predicate
+                                .as_ref()
+                                .map_or(Some(true), |predicate| self.cfgs.eval(predicate));
+
+                            self.parse(items, all([holds, not(taken), predicate]))?;
+                            taken = any([taken, predicate]);
+                        }
+                    } else if let Ok(items) = item.mac.parse_body_with(parse_items) {
+                        // `s!`, `f!`, `extern_ty!` and alike: parse the items they wrap
+                        self.parse(&items, holds)?;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The names of the public `fields` of the structure or union `name` which hold
+    fn fields(
+        &mut self,
+        name: &str,
+        fields: &Punctuated<syn::Field, Token![,]>,
+        holds: Option<bool>,
+    ) -> Vec<String> {
+        fields
+            .iter()
+            .filter(|field| {
+                field.ident.as_ref().is_some_and(|ident| {
+                    self.is_public(format!("{name}.{ident}"), &field.vis, &field.attrs, holds)
+                })
+            })
+            .filter_map(|field| Some(field.ident.as_ref()?.to_string()))
+            .collect()
+    }
+
+    /// Whether the item `name` with `vis` and `attrs` is public, and its `#[cfg]` attributes
+    /// hold; records the item as unknown if that depends on the build script of `libc`
+    fn is_public(
+        &mut self,
+        name: String,
+        vis: &syn::Visibility,
+        attrs: &[Attribute],
+        holds: Option<bool>,
+    ) -> bool {
+        if !matches!(vis, syn::Visibility::Public(_)) {
+            return false;
+        }
+
+        match all([holds, self.cfgs.eval_attrs(attrs)]) {
+            Some(holds) => holds,
+            None => {
+                self.items.unknown.insert(name);
+                false
+            }
+        }
+    }
+}
+
+/// The branches of a `cfg_if!` invocation: the predicate of each - none for a final
+/// `else` - and its items
+struct CfgIf(Vec<(Option<Meta>, Vec<Item>)>);
+
+impl Parse for CfgIf {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut branches = Vec::new();
+
+        loop {
+            // `if #[cfg(...)] { ... }`
+            input.parse::<Token![if]>()?;
+
+            let predicate = match input.call(Attribute::parse_outer)?.as_slice() {
+                [attr] if attr.path().is_ident("cfg") => attr.parse_args()?,
+                _ => return Err(input.error("expected a `#[cfg]` attribute")),
+            };
+
+            let items;
+            braced!(items in input);
+            branches.push((Some(predicate), parse_items(&items)?));
+
+            if input.parse::<Option<Token![else]>>()?.is_none() {
+                break;
+            }
+
+            // A final `else { ... }`
+            if !input.peek(Token![if]) {
+                let items;
+                braced!(items in input);
+                branches.push((None, parse_items(&items)?));
+                break;
+            }
+        }
+
+        Ok(Self(branches))
+    }
+}
+
+fn parse_items(input: ParseStream) -> syn::Result<Vec<Item>> {
+    let mut items = Vec::new();
+
+    while !input.is_empty() {
+        items.push(input.parse()?);
+    }
+
+    Ok(items)
+}
+
+/// Collect the modules `tree` re-exports all the items of, i.e. the last segment of each
+/// `<path>::<module>::*`, `parent` being the segment before `tree`
+fn glob_modules(tree: &syn::UseTree, parent: Option<&syn::Ident>, modules: &mut Vec<String>) {
+    match tree {
+        syn::UseTree::Path(path) => glob_modules(&path.tree, Some(&path.ident), modules),
+        syn::UseTree::Glob(_) => modules.extend(parent.map(ToString::to_string)),
+        syn::UseTree::Group(group) => group
+            .items
+            .iter()
+            .for_each(|tree| glob_modules(tree, parent, modules)),
+        syn::UseTree::Name(_) | syn::UseTree::Rename(_) => (),
+    }
+}
+
+/// The name of a type path, e.g. `c_int` of `crate::c_int` or `::core::ffi::c_int`
+fn type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(path) if path.qself.is_none() => {
+            let segment = path.path.segments.last()?;
+
+            segment
+                .arguments
+                .is_none()
+                .then(|| segment.ident.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Whether `value` is a - optionally negated - integer literal
+fn is_integer(value: &Expr) -> bool {
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(_), ..
+        }) => true,
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => is_integer(expr),
+        _ => false,
+    }
+}
@@ -364,6 +364,19 @@ pub fn build() -> Result<EspIdfBuildOutput> {
         )),
         &main_comp,
     )?;
+    copy_file_if_different(
+        manifest_dir.join(path_buf!(
+            "resources",
+            "cmake_project",
+            "main",
+            "pre_main.lf"
+        )),
+        &main_comp,
+    )?;
+
+    // The linker fragment of the main component collects the `#[pre_main]` functions
+    // for `app_main`; see `esp_idf_sys::pre_main`
+    println!("cargo:rustc-cfg=esp_idf_sys_pre_main");
 
     // Generate the `idf_component.yml` for the main component if there is at least one
     // remote component.
@@ -0,0 +1,121 @@
+//! Linking of the patches to ESP-IDF implemented in Rust (see `src/patches`).
+//!
+//! Unless the application calls `esp_idf_sys::link_patches()`, nothing in the Rust code
+//! references the `#[no_mangle]` patch functions, so the linker would only pull them from
+//! the `esp-idf-sys` rlib if some other archive member already did. Marking their symbols
+//! as undefined makes the linker look for them right away, before the - unpatched -
+//! ESP-IDF libraries.
+
+use std::path::Path;
+use std::{env, fs};
+
+use anyhow::Result;
+use embuild::build;
+
+/// The patches to functions which only some ESP-IDF versions lack, along with the ESP-IDF
+/// components defining these functions in the versions which do have them.
+///
+/// Each patch is compiled in - with the `esp_idf_sys_patch_<function>` cfg - only if the
+/// sources of the ESP-IDF being built have no definition of its function (see
+/// [`configure_optional`]).
+const OPTIONAL_PATCHES: &[(&str, &[&str])] = &[
+    ("getrandom", LIBC_COMPONENTS),
+    ("getentropy", LIBC_COMPONENTS),
+    ("clock_nanosleep", LIBC_COMPONENTS),
+    ("pthread_setname_np", &["pthread"]),
+    ("realpath", &["newlib", "esp_libc", "vfs"]),
+];
+
+/// The ESP-IDF components of the C library support, under both of their names
+const LIBC_COMPONENTS: &[&str] = &["newlib", "esp_libc"];
+
+/// The directories of the ESP-IDF components which only contain tests
+const TEST_DIRS: &[&str] = &["test", "test_apps", "host_test"];
+
+/// Find the functions of [`OPTIONAL_PATCHES`] which the ESP-IDF at `esp_idf` lacks, and
+/// output the `esp_idf_sys_patch_<function>` cfgs compiling their patches in.
+///
+/// The functions are looked up in the C sources of ESP-IDF rather than in the bindings,
+/// as the C library headers also declare functions which ESP-IDF does not implement.
+pub fn configure_optional(esp_idf: &Path) -> Result<Vec<&'static str>> {
+    let mut missing = Vec::new();
+
+    for (function, components) in OPTIONAL_PATCHES {
+        // A function definition, as opposed to a declaration or a call: the return type and
+        // the name of the function at the beginning of a line, and the parameters followed
+        // by the body
+        let definition = regex::Regex::new(&format!(
+            r"(?m)^[A-Za-z_][\w \t*]*\b{function}\s*\([^;{{}}]*\{{"
+        ))?;
+
+        let mut defined = false;
+        for component in *components {
+            let dir = esp_idf.join("components").join(component);
+
+            if dir.is_dir() && defines(&dir, &definition)? {
+                defined = true;
+                break;
+            }
+        }
+
+        if !defined {
+            println!("cargo:rustc-cfg=esp_idf_sys_patch_{function}");
+            missing.push(*function);
+        }
+    }
+
+    Ok(missing)
+}
+
+/// Whether a C source file in `dir` - or in a directory below it, other than a test one -
+/// matches `definition`
+fn defines(dir: &Path, definition: &regex::Regex) -> Result<bool> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            let test = path
+                .file_name()
+                .is_some_and(|name| TEST_DIRS.iter().any(|test| name == *test));
+
+            if !test && defines(&path, definition)? {
+                return Ok(true);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "c")
+            && definition.is_match(&String::from_utf8_lossy(&fs::read(&path)?))
+        {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// The symbols of the patches compiled into `esp-idf-sys`, `optional` being the functions
+/// of the optional patches the ESP-IDF lacks
+pub fn symbols(optional: &[&'static str]) -> Vec<&'static str> {
+    let mut symbols = vec![
+        "atexit",
+        "__cxa_atexit",
+        "at_quick_exit",
+        "exit",
+        "quick_exit",
+    ];
+
+    if env::var("CARGO_FEATURE_STD").is_ok() {
+        symbols.extend(["lstat", "readlink", "symlink"]);
+        symbols.extend(optional);
+    }
+
+    symbols
+}
+
+/// Add an `--undefined` linker argument for each patch symbol to `link_args`, so that
+/// the patches are linked without calling `esp_idf_sys::link_patches()`.
+pub fn configure(link_args: &mut build::LinkArgs, optional: &[&'static str]) {
+    link_args.args.extend(
+        symbols(optional)
+            .into_iter()
+            .map(|symbol| format!("-Wl,--undefined={symbol}")),
+    );
+}
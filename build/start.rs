@@ -0,0 +1,227 @@
+//! Configuration of the `binstart`/`libstart` glue: the FreeRTOS task the Rust `main`
+//! function is spawned on, the action taken once it returns, and its arguments.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+
+use anyhow::{bail, Result};
+use embuild::{build, cargo};
+
+use crate::config::BuildConfig;
+
+/// The smallest stack size accepted for the main task; anything below cannot even
+/// run the `std` runtime initialization
+const MIN_STACK_SIZE: u32 = 2048;
+
+/// The supported `esp_idf_sys_main_exit` actions
+const EXIT_ACTIONS: &[&str] = &["return", "restart", "deep_sleep", "spin"];
+
+/// The maximum length of an NVS namespace or key
+const NVS_KEY_MAX_LEN: usize = 15;
+
+/// Configure the `binstart`/`libstart` glue; see [`main_task`], [`main_exit`] and
+/// [`main_args`].
+pub fn configure(config: &BuildConfig, cfg_args: &build::CfgArgs) -> Result<()> {
+    let start =
+        env::var("CARGO_FEATURE_BINSTART").is_ok() || env::var("CARGO_FEATURE_LIBSTART").is_ok();
+
+    main_task(config, cfg_args, start)?;
+    main_exit(config, start)?;
+    main_args(config)?;
+
+    Ok(())
+}
+
+/// If any of the main task options is set, emit the `esp_idf_sys_main_task` cfg and
+/// generate the `MAIN_TASK_*` constants into `main_task.rs` in `OUT_DIR`.
+///
+/// Unset options default to the ones of the ESP-IDF main task, except for the core
+/// affinity, which defaults to none.
+fn main_task(config: &BuildConfig, cfg_args: &build::CfgArgs, start: bool) -> Result<()> {
+    let BuildConfig {
+        esp_idf_sys_main_task_stack_size: stack_size,
+        esp_idf_sys_main_task_priority: priority,
+        esp_idf_sys_main_task_core: core,
+        ..
+    } = config;
+
+    if stack_size.is_none() && priority.is_none() && core.is_none() {
+        return Ok(());
+    }
+
+    let core = core.as_ref().map(|core| core.core()).transpose()?.flatten();
+
+    if let Some(stack_size) = stack_size {
+        if *stack_size < MIN_STACK_SIZE {
+            bail!("main task stack size {stack_size} is too small, expected at least {MIN_STACK_SIZE} bytes");
+        }
+    }
+
+    if let Some(core) = core {
+        let cores = if cfg_args.get("esp_idf_freertos_unicore").is_some() {
+            1
+        } else {
+            2
+        };
+
+        if core >= cores {
+            bail!("main task core {core} does not exist, the FreeRTOS configuration has {cores} core(s)");
+        }
+    }
+
+    if !start {
+        cargo::print_warning(
+            "(esp-idf-sys) The main task options have no effect without the `binstart` or `libstart` features",
+        );
+    }
+
+    let mut output = String::new();
+
+    writeln!(
+        output,
+        "/// The stack size (in bytes) of the task the Rust `main` function runs on"
+    )?;
+    match stack_size {
+        Some(stack_size) => writeln!(
+            output,
+            "pub const MAIN_TASK_STACK_SIZE: u32 = {stack_size};"
+        )?,
+        None => writeln!(
+            output,
+            "pub const MAIN_TASK_STACK_SIZE: u32 = crate::CONFIG_ESP_MAIN_TASK_STACK_SIZE as _;"
+        )?,
+    }
+
+    writeln!(
+        output,
+        "/// The FreeRTOS priority of the task the Rust `main` function runs on"
+    )?;
+    match priority {
+        Some(priority) => writeln!(output, "pub const MAIN_TASK_PRIORITY: u32 = {priority};")?,
+        None => writeln!(
+            output,
+            "pub const MAIN_TASK_PRIORITY: u32 = crate::ESP_TASK_MAIN_PRIO as _;"
+        )?,
+    }
+
+    writeln!(
+        output,
+        "/// The core the task the Rust `main` function runs on is pinned to, if any"
+    )?;
+    writeln!(output, "pub const MAIN_TASK_CORE: Option<u32> = {core:?};")?;
+
+    fs::write(cargo::out_dir().join("main_task.rs"), output)?;
+
+    println!("cargo:rustc-cfg=esp_idf_sys_main_task");
+
+    Ok(())
+}
+
+/// If the exit action is set, emit it as the `esp_idf_sys_main_exit="<action>"` cfg,
+/// which selects the default `MainExitAction`.
+fn main_exit(config: &BuildConfig, start: bool) -> Result<()> {
+    let Some(action) = config.esp_idf_sys_main_exit.as_deref() else {
+        return Ok(());
+    };
+
+    let action = action.trim().to_lowercase();
+
+    if !EXIT_ACTIONS.contains(&action.as_str()) {
+        bail!(
+            "invalid main exit action '{action}', expected one of {}",
+            EXIT_ACTIONS.join(", ")
+        );
+    }
+
+    if !start {
+        cargo::print_warning(
+            "(esp-idf-sys) The main exit action has no effect without the `binstart` or `libstart` features",
+        );
+    }
+
+    println!("cargo:rustc-cfg=esp_idf_sys_main_exit=\"{action}\"");
+
+    Ok(())
+}
+
+/// If any of the main arguments options is set, emit the `esp_idf_sys_main_args` cfg
+/// and generate the `MAIN_ARGS` and `MAIN_ARGS_NVS` constants into `main_args.rs` in
+/// `OUT_DIR`.
+fn main_args(config: &BuildConfig) -> Result<()> {
+    let BuildConfig {
+        esp_idf_sys_main_args: args,
+        esp_idf_sys_main_args_nvs: nvs,
+        ..
+    } = config;
+
+    if args.is_none() && nvs.is_none() {
+        return Ok(());
+    }
+
+    let args = args
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>();
+
+    if args.iter().any(|arg| arg.contains('\0')) {
+        bail!("the main arguments must not contain NUL characters");
+    }
+
+    let nvs = nvs
+        .as_deref()
+        .map(|nvs| match nvs.trim().split_once('/') {
+            Some((namespace, key))
+                if (1..=NVS_KEY_MAX_LEN).contains(&namespace.len())
+                    && (1..=NVS_KEY_MAX_LEN).contains(&key.len())
+                    && !nvs.contains('\0') =>
+            {
+                Ok((namespace, key))
+            }
+            _ => bail!(
+                "invalid main arguments NVS location '{nvs}', expected `<namespace>/<key>` \
+                 with at most {NVS_KEY_MAX_LEN} characters each"
+            ),
+        })
+        .transpose()?;
+
+    if env::var("CARGO_FEATURE_STD").is_err() || env::var("CARGO_FEATURE_BINSTART").is_err() {
+        cargo::print_warning(
+            "(esp-idf-sys) The main arguments have no effect without the `std` and `binstart` features",
+        );
+    }
+
+    let mut output = String::new();
+
+    writeln!(
+        output,
+        "/// The arguments passed to the Rust `main` function after the program name"
+    )?;
+    write!(output, "pub const MAIN_ARGS: &[&core::ffi::CStr] = &[")?;
+    for arg in args {
+        write!(output, "c{arg:?}, ")?;
+    }
+    writeln!(output, "];")?;
+
+    writeln!(
+        output,
+        "/// The NVS namespace and key of the arguments which - if present - replace `MAIN_ARGS`"
+    )?;
+    match nvs {
+        Some((namespace, key)) => writeln!(
+            output,
+            "pub const MAIN_ARGS_NVS: Option<(&core::ffi::CStr, &core::ffi::CStr)> = Some((c{namespace:?}, c{key:?}));"
+        )?,
+        None => writeln!(
+            output,
+            "pub const MAIN_ARGS_NVS: Option<(&core::ffi::CStr, &core::ffi::CStr)> = None;"
+        )?,
+    }
+
+    fs::write(cargo::out_dir().join("main_args.rs"), output)?;
+
+    println!("cargo:rustc-cfg=esp_idf_sys_main_args");
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
 //! This example does not use anything from the `esp-idf-sys` unsafe API
 //! but demonstrates, that *linking* with the `esp-idf-sys` library artefacts (and with the Rust Standard Library)
 //! does provide the Rust STD layer on top of ESP IDF!
+//!
+//! (The only exception being the `lstat` hook of the `esp-idf-sys` patches for VFS drivers supporting links,
+//! which the filesystem tests register a fake driver with.)
 
 #![allow(unknown_lints)]
 #![allow(unexpected_cfgs)]
@@ -30,7 +33,6 @@ fn main() -> Result<(), io::Error> {
 
     test_threads()?;
 
-    #[cfg(not(esp_idf_version = "4.3"))]
     test_fs()?;
 
     loop {
@@ -115,10 +117,21 @@ fn test_threads() -> Result<(), io::Error> {
     Ok(())
 }
 
-#[cfg(not(esp_idf_version = "4.3"))]
 fn test_fs() -> Result<(), io::Error> {
+    test_fs_canonicalize()?;
+
+    test_fs_links()?;
+
+    test_fs_link_drivers()?;
+
+    Ok(())
+}
+
+#[cfg(not(esp_idf_sys_patch_realpath))]
+fn test_fs_canonicalize() -> Result<(), io::Error> {
     use std::{fs, path::PathBuf};
 
+    // ESP-IDF resolves paths lexically, relative to `/`, and without checking that they exist
     assert_eq!(fs::canonicalize(PathBuf::from("."))?, PathBuf::from("/"));
     assert_eq!(
         fs::canonicalize(
@@ -131,6 +144,147 @@ fn test_fs() -> Result<(), io::Error> {
         )?,
         PathBuf::from("/foo/baz")
     );
+    assert_eq!(
+        fs::canonicalize(PathBuf::from("foo").join(".").join("bar").join(".."))?,
+        PathBuf::from("/foo")
+    );
+    assert_eq!(fs::canonicalize(PathBuf::from("/.."))?, PathBuf::from("/"));
+    assert_eq!(
+        fs::canonicalize(PathBuf::from("//foo//bar/"))?,
+        PathBuf::from("/foo/bar")
+    );
+
+    Ok(())
+}
+
+#[cfg(esp_idf_sys_patch_realpath)]
+fn test_fs_canonicalize() -> Result<(), io::Error> {
+    use std::{fs, path::PathBuf};
+
+    // ESP-IDF has no `realpath` (V4.3), and the `esp-idf-sys` patch for it does not resolve paths either
+    let err = fs::canonicalize(PathBuf::from(".")).unwrap_err();
+
+    assert_eq!(err.raw_os_error(), Some(esp_idf_sys::ENOSYS as _));
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+
+    Ok(())
+}
+
+fn test_fs_links() -> Result<(), io::Error> {
+    use std::{fs, os::unix, path::PathBuf};
+
+    // Nothing is mounted at `/`, and none of the ESP-IDF filesystems supports symbolic links
+    let missing = PathBuf::from("/missing");
+
+    assert_eq!(
+        fs::symlink_metadata(&missing).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+    assert_eq!(
+        fs::read_link(&missing).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+    assert_eq!(
+        unix::fs::symlink("/foo", &missing).unwrap_err().kind(),
+        io::ErrorKind::Unsupported
+    );
+
+    Ok(())
+}
+
+fn test_fs_link_drivers() -> Result<(), io::Error> {
+    use core::ffi::{c_char, c_int, CStr};
+    use std::fs;
+
+    use esp_idf_sys::{
+        register_lstat, stat, unregister_lstat, ESP_ERR_INVALID_STATE, ESP_ERR_NO_MEM, S_IFDIR,
+        S_IFLNK, S_IFREG,
+    };
+
+    // The `lstat` of a fake VFS driver supporting links, which sees the paths relative to its base path:
+    // its root is a directory, `/link` is a link, and anything else a regular file
+    unsafe extern "C" fn fake_lstat(path: *const c_char, buf: *mut stat) -> c_int {
+        let mode = match CStr::from_ptr(path).to_bytes() {
+            b"" => S_IFDIR,
+            b"/link" => S_IFLNK,
+            _ => S_IFREG,
+        };
+
+        *buf = core::mem::zeroed();
+        (*buf).st_mode = mode as _;
+
+        0
+    }
+
+    // As many base paths as there are `lstat` slots, the nested ones taking precedence for their paths
+    const BASE_PATHS: [&CStr; 8] = [
+        c"/links",
+        c"/links/0",
+        c"/links/1",
+        c"/links/2",
+        c"/links/3",
+        c"/links/4",
+        c"/links/5",
+        c"/links/6",
+    ];
+
+    for base_path in BASE_PATHS {
+        register_lstat(base_path, fake_lstat)?;
+    }
+
+    // All the slots are taken, and a base path can only be registered once
+    assert_eq!(
+        register_lstat(c"/links/7", fake_lstat).unwrap_err().code(),
+        ESP_ERR_NO_MEM
+    );
+    assert_eq!(
+        register_lstat(c"/links", fake_lstat).unwrap_err().code(),
+        ESP_ERR_INVALID_STATE
+    );
+
+    assert!(fs::symlink_metadata("/links")?.is_dir());
+    assert!(fs::symlink_metadata("/links/link")?
+        .file_type()
+        .is_symlink());
+    // `/links/0` is the base path of the path, with `/link` relative to it, rather than `/links`
+    assert!(fs::symlink_metadata("/links/0/link")?
+        .file_type()
+        .is_symlink());
+    assert!(fs::symlink_metadata("/links/0/file")?.is_file());
+    assert!(fs::symlink_metadata("/links/7/link")?.is_file());
+
+    // Only whole path components match a base path, other paths are `stat`ed
+    assert_eq!(
+        fs::symlink_metadata("/links0/link").unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+
+    // Links are reported, but cannot be read
+    assert_eq!(
+        fs::read_link("/links/0/link").unwrap_err().kind(),
+        io::ErrorKind::Unsupported
+    );
+    assert_eq!(
+        fs::read_link("/links/0/file").unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+
+    for base_path in BASE_PATHS {
+        unregister_lstat(base_path)?;
+    }
+
+    assert_eq!(
+        unregister_lstat(c"/links").unwrap_err().code(),
+        ESP_ERR_INVALID_STATE
+    );
+    assert_eq!(
+        fs::symlink_metadata("/links/link").unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+
+    // The slots are free again
+    register_lstat(c"/links/7", fake_lstat)?;
+    unregister_lstat(c"/links/7")?;
 
     Ok(())
 }
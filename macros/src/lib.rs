@@ -0,0 +1,119 @@
+//! Procedural macros of [`esp-idf-sys`](https://docs.rs/esp-idf-sys), re-exported by it.
+
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Error, ItemFn, LitInt, LitStr, Path, ReturnType};
+
+/// The priority of the pre-main functions which do not specify one
+const DEFAULT_PRIORITY: u16 = 1000;
+
+/// Registers a function to be called before the Rust `main` function.
+///
+/// With the `binstart` or `libstart` features of `esp-idf-sys`, `app_main` calls the
+/// registered functions of all crates right after binding the standard streams with
+/// `restore_posix_stdio_fds`, and before the Rust `main` function. This allows library
+/// crates to initialize themselves (NVS, logging, watchdogs, ...) without every binary
+/// having to call them.
+///
+/// The function must take no arguments and return `()`. The functions are called
+/// in ascending order of their priority (`0` to `65535`, `1000` by default), given
+/// as `#[pre_main(priority = 100)]`; the order of functions with the same priority
+/// is unspecified.
+///
+/// ```ignore
+/// #[esp_idf_sys::pre_main(priority = 100)]
+/// fn init_nvs() {
+///     esp_idf_sys::esp!(unsafe { esp_idf_sys::nvs_flash_init() }).unwrap();
+/// }
+/// ```
+///
+/// When `esp-idf-sys` is not a dependency of the crate under its own name - e.g. when
+/// it is used through the re-export of another crate - its path is given as
+/// `#[pre_main(crate = "esp_idf_svc::sys")]`.
+///
+/// The registry relies on a linker fragment of the ESP-IDF build done by `esp-idf-sys`,
+/// so the attribute is only supported with its `native` builder; it fails the build
+/// with the `pio` builder or as a component of an ESP-IDF CMake project, where the
+/// linker would not collect the functions.
+///
+/// Note that the registry entries are only linked if the object files of the crate
+/// defining them are. With `binstart`, `rustc` takes care of that for the entries of
+/// all crates; with `libstart` however, the application is a static library, from which
+/// the linker of the ESP-IDF build only pulls the object files which the rest of the
+/// program references. A library crate with `#[pre_main]` functions and no other items
+/// used by the application has to be referenced explicitly in that case, e.g. by
+/// calling one of its functions.
+#[proc_macro_attribute]
+pub fn pre_main(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut priority = DEFAULT_PRIORITY;
+    let mut krate: Path = syn::parse_quote!(::esp_idf_sys);
+
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("priority") {
+            priority = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+            Ok(())
+        } else if meta.path.is_ident("crate") {
+            krate = meta.value()?.parse::<LitStr>()?.parse()?;
+            Ok(())
+        } else {
+            Err(meta.error("unsupported `pre_main` argument, expected `priority` or `crate`"))
+        }
+    });
+
+    parse_macro_input!(args with parser);
+
+    let function = parse_macro_input!(item as ItemFn);
+
+    if let Err(err) = check(&function) {
+        return err.to_compile_error().into();
+    }
+
+    let name = &function.sig.ident;
+    let entry = format_ident!("__ESP_IDF_SYS_PRE_MAIN_{}", name.to_string().to_uppercase());
+
+    // The linker sorts the entries by the numeric suffix of their section name
+    let section = format!(".esp_idf_sys_pre_main.{priority:05}");
+
+    quote! {
+        #function
+
+        #[used]
+        #[doc(hidden)]
+        #[link_section = #section]
+        static #entry: #krate::PreMain = #krate::PreMain::new(#name);
+    }
+    .into()
+}
+
+fn check(function: &ItemFn) -> Result<(), Error> {
+    let sig = &function.sig;
+
+    if !sig.inputs.is_empty() {
+        return Err(Error::new(
+            sig.inputs.span(),
+            "`pre_main` functions must not take arguments",
+        ));
+    }
+
+    if !matches!(sig.output, ReturnType::Default) {
+        return Err(Error::new(
+            sig.output.span(),
+            "`pre_main` functions must return `()`",
+        ));
+    }
+
+    if sig.asyncness.is_some()
+        || sig.unsafety.is_some()
+        || sig.abi.is_some()
+        || !sig.generics.params.is_empty()
+        || sig.variadic.is_some()
+    {
+        return Err(Error::new(
+            sig.span(),
+            "`pre_main` functions must be plain, non-generic `fn`s",
+        ));
+    }
+
+    Ok(())
+}
@@ -1,33 +1,508 @@
-#![cfg(all(not(feature = "std"), feature = "alloc_handler"))]
+//! A heap allocator on top of the capability-based `heap_caps_*` functions of ESP-IDF.
+//!
+//! [`EspAllocator`] honors the alignment of the allocations, and can place
+//! allocations in specific memory - internal, DMA-capable, PSRAM (SPIRAM) - or
+//! split them between PSRAM and internal memory by their size. It can be used as
+//! the `#[global_allocator]` - with and without `std` - and - with the
+//! `allocator_api` feature - for individual collections, e.g.
+//! `Vec::new_in(EspAllocator::DMA)`.
+//!
+//! With the `alloc_handler` feature and without `std`, this crate defines
+//! [`EspAllocator::DEFAULT`] as the global allocator.
+//!
+//! [`HeapStats`] reports the state of the heap memory with specific capabilities.
+//! With the `alloc_stats` feature, it includes the counters kept by `EspAllocator`
+//! for the capabilities of each allocator.
 
 use core::alloc::{GlobalAlloc, Layout};
+use core::ptr;
 
 use crate::*;
 
-#[cfg_attr(not(feature = "std"), global_allocator)]
-#[allow(dead_code)]
-static HEAP: Esp32Alloc = Esp32Alloc;
+#[cfg(all(not(feature = "std"), feature = "alloc_handler"))]
+#[global_allocator]
+static HEAP: EspAllocator = EspAllocator::DEFAULT;
 
-#[cfg_attr(not(feature = "std"), alloc_error_handler)]
-#[allow(dead_code)]
-fn on_oom(_layout: Layout) -> ! {
-    unsafe { abort() }
+#[cfg(all(not(feature = "std"), feature = "alloc_handler"))]
+#[alloc_error_handler]
+fn on_oom(layout: Layout) -> ! {
+    unsafe {
+        esp_log_write(
+            esp_log_level_t_ESP_LOG_ERROR,
+            c"alloc".as_ptr(),
+            c"E (%u) alloc: Allocating %u bytes aligned to %u failed, the largest free block has %u bytes\n"
+                .as_ptr(),
+            esp_log_timestamp(),
+            layout.size() as core::ffi::c_uint,
+            layout.align() as core::ffi::c_uint,
+            heap_caps_get_largest_free_block(HEAP.caps()) as core::ffi::c_uint,
+        );
+
+        abort()
+    }
 }
 
-struct Esp32Alloc;
+/// The alignment of the memory returned by `heap_caps_*` with any heap
+const HEAP_ALIGN: usize = 4;
 
-unsafe impl Sync for Esp32Alloc {}
+/// The alignment up to which allocations go through `heap_caps_malloc`, i.e. the one
+/// of `max_align_t`; allocations with a larger alignment go through
+/// `heap_caps_aligned_alloc`, as do the ones `heap_caps_malloc` happens to misalign.
+///
+/// Before ESP-IDF v5.0, the memory of `heap_caps_aligned_alloc` has to be freed with
+/// `heap_caps_aligned_free`, so the allocations with an alignment above [`HEAP_ALIGN`]
+/// cannot take that route.
+#[cfg(esp_idf_version_at_least_5_0_0)]
+const MIN_ALIGN: usize = 8;
+#[cfg(not(esp_idf_version_at_least_5_0_0))]
+const MIN_ALIGN: usize = HEAP_ALIGN;
 
-unsafe impl GlobalAlloc for Esp32Alloc {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        heap_caps_malloc(layout.size(), MALLOC_CAP_8BIT as _) as *mut _
+/// A heap allocator allocating memory with specific capabilities (`MALLOC_CAP_*`)
+/// via the `heap_caps_*` functions of ESP-IDF.
+///
+/// By default, all allocations are done from memory with the capabilities of the
+/// allocator. With [`EspAllocator::with_spiram_threshold`], allocations of at least
+/// the threshold size are done from PSRAM first, falling back to internal memory,
+/// while smaller allocations are done from internal memory first, falling back to
+/// any memory with the capabilities of the allocator.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct EspAllocator {
+    caps: u32,
+    spiram_threshold: Option<usize>,
+}
+
+impl EspAllocator {
+    /// Allocates byte-addressable memory, just like `malloc`
+    pub const DEFAULT: Self = Self::new(MALLOC_CAP_8BIT);
+
+    /// Allocates internal memory only
+    pub const INTERNAL: Self = Self::new(MALLOC_CAP_INTERNAL | MALLOC_CAP_8BIT);
+
+    /// Allocates DMA-capable memory only
+    pub const DMA: Self = Self::new(MALLOC_CAP_DMA | MALLOC_CAP_8BIT);
+
+    /// Allocates PSRAM (SPIRAM) only
+    pub const SPIRAM: Self = Self::new(MALLOC_CAP_SPIRAM | MALLOC_CAP_8BIT);
+
+    /// Creates an allocator allocating memory with the `MALLOC_CAP_*` capabilities `caps`.
+    pub const fn new(caps: u32) -> Self {
+        Self {
+            caps,
+            spiram_threshold: None,
+        }
+    }
+
+    /// Places the allocations of at least `threshold` bytes in PSRAM first, and
+    /// the smaller ones in internal memory first; see [`EspAllocator`].
+    pub const fn with_spiram_threshold(self, threshold: usize) -> Self {
+        Self {
+            spiram_threshold: Some(threshold),
+            ..self
+        }
+    }
+
+    /// The `MALLOC_CAP_*` capabilities of the memory allocated by this allocator
+    pub const fn caps(&self) -> u32 {
+        self.caps
+    }
+
+    /// The PSRAM threshold of this allocator, if any
+    pub const fn spiram_threshold(&self) -> Option<usize> {
+        self.spiram_threshold
+    }
+
+    /// The capabilities to allocate `size` bytes with, and - should that fail - the
+    /// capabilities to fall back to
+    fn caps_for(&self, size: usize) -> (u32, Option<u32>) {
+        match self.spiram_threshold {
+            None => (self.caps, None),
+            Some(threshold) if size >= threshold => (
+                self.caps | MALLOC_CAP_SPIRAM,
+                Some(self.caps | MALLOC_CAP_INTERNAL),
+            ),
+            Some(_) => (self.caps | MALLOC_CAP_INTERNAL, Some(self.caps)),
+        }
+    }
+
+    unsafe fn allocate_with(&self, layout: Layout, caps: u32) -> *mut u8 {
+        if layout.align() <= MIN_ALIGN {
+            let ptr = heap_caps_malloc(layout.size() as _, caps) as *mut u8;
+
+            if ptr as usize % layout.align() == 0 {
+                return ptr;
+            }
+
+            // Only aligned to `HEAP_ALIGN`
+            heap_caps_free(ptr as *mut _);
+        }
+
+        heap_caps_aligned_alloc(layout.align() as _, layout.size() as _, caps) as *mut _
+    }
+
+    /// The statistics of the heap memory with the capabilities of this allocator
+    pub fn stats(&self) -> HeapStats {
+        HeapStats::of(self.caps)
+    }
+
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.allocate_uncounted(layout);
+
+        #[cfg(feature = "alloc_stats")]
+        counters::allocated(self.caps, layout.size(), !ptr.is_null());
+
+        ptr
     }
 
-    unsafe fn realloc(&self, ptr: *mut u8, _layout: Layout, new_size: usize) -> *mut u8 {
-        heap_caps_realloc(ptr as *mut _, new_size, MALLOC_CAP_8BIT as _) as *mut _
+    unsafe fn allocate_uncounted(&self, layout: Layout) -> *mut u8 {
+        let (caps, fallback) = self.caps_for(layout.size());
+
+        let ptr = self.allocate_with(layout, caps);
+
+        match fallback {
+            Some(fallback) if ptr.is_null() => self.allocate_with(layout, fallback),
+            _ => ptr,
+        }
     }
 
-    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+    unsafe fn free(&self, ptr: *mut u8, layout: Layout) {
+        self.free_uncounted(ptr, layout);
+
+        #[cfg(feature = "alloc_stats")]
+        counters::deallocated(self.caps, layout.size());
+    }
+
+    unsafe fn free_uncounted(&self, ptr: *mut u8, layout: Layout) {
+        // Before ESP-IDF v5.0, aligned allocations carry a header of their own
+        #[cfg(not(esp_idf_version_at_least_5_0_0))]
+        if layout.align() > MIN_ALIGN {
+            heap_caps_aligned_free(ptr as *mut _);
+            return;
+        }
+
+        let _ = layout;
+
         heap_caps_free(ptr as *mut _);
     }
+
+    unsafe fn reallocate(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.reallocate_uncounted(ptr, layout, new_size);
+
+        #[cfg(feature = "alloc_stats")]
+        counters::reallocated(self.caps, layout.size(), new_size, !new_ptr.is_null());
+
+        new_ptr
+    }
+
+    unsafe fn reallocate_uncounted(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        if layout.align() > HEAP_ALIGN {
+            // `heap_caps_realloc` does not preserve the alignment
+            let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+
+            let new_ptr = self.allocate_uncounted(new_layout);
+            if !new_ptr.is_null() {
+                ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+                self.free_uncounted(ptr, layout);
+            }
+
+            return new_ptr;
+        }
+
+        let (caps, fallback) = self.caps_for(new_size);
+
+        let new_ptr = heap_caps_realloc(ptr as *mut _, new_size as _, caps) as *mut u8;
+
+        match fallback {
+            // A failed `heap_caps_realloc` leaves the original allocation intact
+            Some(fallback) if new_ptr.is_null() => {
+                heap_caps_realloc(ptr as *mut _, new_size as _, fallback) as *mut _
+            }
+            _ => new_ptr,
+        }
+    }
+}
+
+impl Default for EspAllocator {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+unsafe impl GlobalAlloc for EspAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.allocate(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.reallocate(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.free(ptr, layout)
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+unsafe impl core::alloc::Allocator for EspAllocator {
+    fn allocate(&self, layout: Layout) -> Result<ptr::NonNull<[u8]>, core::alloc::AllocError> {
+        if layout.size() == 0 {
+            // A dangling, yet well-aligned, pointer
+            let dangling = unsafe { ptr::NonNull::new_unchecked(layout.align() as *mut u8) };
+
+            return Ok(ptr::NonNull::slice_from_raw_parts(dangling, 0));
+        }
+
+        ptr::NonNull::new(unsafe { EspAllocator::allocate(self, layout) })
+            .map(|ptr| ptr::NonNull::slice_from_raw_parts(ptr, layout.size()))
+            .ok_or(core::alloc::AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: ptr::NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            self.free(ptr.as_ptr(), layout);
+        }
+    }
+}
+
+/// The capabilities of the regions reported by [`HeapStats::regions`], besides the
+/// ones of the allocators with counters
+const REGIONS: &[u32] = &[
+    EspAllocator::DEFAULT.caps(),
+    EspAllocator::INTERNAL.caps(),
+    EspAllocator::DMA.caps(),
+    EspAllocator::SPIRAM.caps(),
+];
+
+/// The statistics of the heap memory with specific `MALLOC_CAP_*` capabilities, as
+/// reported by `heap_caps_get_info`, together with the counters of the
+/// [`EspAllocator`]s with these capabilities.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct HeapStats {
+    /// The `MALLOC_CAP_*` capabilities of the memory
+    pub caps: u32,
+    /// The free bytes in all heaps with these capabilities
+    pub free_bytes: usize,
+    /// The allocated bytes in all heaps with these capabilities, by Rust and C code
+    pub allocated_bytes: usize,
+    /// The size of the largest free block
+    pub largest_free_block: usize,
+    /// The lowest number of free bytes since boot
+    pub minimum_free_bytes: usize,
+    /// The number of allocated blocks
+    pub allocated_blocks: usize,
+    /// The number of free blocks
+    pub free_blocks: usize,
+    /// The total number of blocks
+    pub total_blocks: usize,
+    /// The counters of the `EspAllocator`s with exactly these capabilities; `None`
+    /// without the `alloc_stats` feature, or if no such allocator has been used yet
+    pub counters: Option<AllocCounters>,
+}
+
+impl HeapStats {
+    /// The statistics of the heap memory with the `MALLOC_CAP_*` capabilities `caps`
+    pub fn of(caps: u32) -> Self {
+        let mut info: multi_heap_info_t = Default::default();
+
+        unsafe {
+            heap_caps_get_info(&mut info, caps);
+        }
+
+        #[cfg(feature = "alloc_stats")]
+        let counters = counters::get(caps);
+        #[cfg(not(feature = "alloc_stats"))]
+        let counters = None;
+
+        Self {
+            caps,
+            free_bytes: info.total_free_bytes as _,
+            allocated_bytes: info.total_allocated_bytes as _,
+            largest_free_block: info.largest_free_block as _,
+            minimum_free_bytes: info.minimum_free_bytes as _,
+            allocated_blocks: info.allocated_blocks as _,
+            free_blocks: info.free_blocks as _,
+            total_blocks: info.total_blocks as _,
+            counters,
+        }
+    }
+
+    /// The statistics of the memory of the predefined [`EspAllocator`]s (default,
+    /// internal, DMA-capable and PSRAM), followed by the ones of the capabilities
+    /// of all other allocators with counters
+    pub fn regions() -> HeapRegions {
+        HeapRegions { index: 0 }
+    }
+}
+
+/// An iterator over the statistics of the heap regions; see [`HeapStats::regions`]
+#[derive(Clone, Debug)]
+pub struct HeapRegions {
+    index: usize,
+}
+
+impl Iterator for HeapRegions {
+    type Item = HeapStats;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(caps) = REGIONS.get(self.index) {
+            self.index += 1;
+
+            return Some(HeapStats::of(*caps));
+        }
+
+        #[cfg(feature = "alloc_stats")]
+        while let Some(caps) = counters::caps(self.index - REGIONS.len()) {
+            self.index += 1;
+
+            if let Some(caps) = caps.filter(|caps| !REGIONS.contains(caps)) {
+                return Some(HeapStats::of(caps));
+            }
+        }
+
+        None
+    }
+}
+
+/// The counters kept by the [`EspAllocator`]s with the same capabilities, with the
+/// `alloc_stats` feature.
+///
+/// The byte counts are the sizes requested from the allocators, without the
+/// overhead of the heap.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct AllocCounters {
+    /// The currently allocated bytes
+    pub current_bytes: usize,
+    /// The highest number of allocated bytes so far
+    pub peak_bytes: usize,
+    /// The number of successful allocations
+    pub allocations: usize,
+    /// The number of deallocations
+    pub deallocations: usize,
+    /// The number of failed allocations and reallocations
+    pub failed_allocations: usize,
+}
+
+#[cfg(feature = "alloc_stats")]
+mod counters {
+    use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+    use super::AllocCounters;
+
+    /// The number of distinct capabilities which can be counted; the allocations of
+    /// any further capabilities are not counted
+    const SLOTS: usize = 8;
+
+    /// The capabilities of an unclaimed slot; `MALLOC_CAP_INVALID` is never allocated with
+    const FREE: u32 = crate::MALLOC_CAP_INVALID;
+
+    struct Slot {
+        caps: AtomicU32,
+        current: AtomicUsize,
+        peak: AtomicUsize,
+        allocations: AtomicUsize,
+        deallocations: AtomicUsize,
+        failed: AtomicUsize,
+    }
+
+    impl Slot {
+        const fn new() -> Self {
+            Self {
+                caps: AtomicU32::new(FREE),
+                current: AtomicUsize::new(0),
+                peak: AtomicUsize::new(0),
+                allocations: AtomicUsize::new(0),
+                deallocations: AtomicUsize::new(0),
+                failed: AtomicUsize::new(0),
+            }
+        }
+
+        fn grow(&self, size: usize) {
+            let current = self.current.fetch_add(size, Ordering::Relaxed) + size;
+            self.peak.fetch_max(current, Ordering::Relaxed);
+        }
+
+        fn shrink(&self, size: usize) {
+            self.current.fetch_sub(size, Ordering::Relaxed);
+        }
+    }
+
+    static COUNTERS: [Slot; SLOTS] = [const { Slot::new() }; SLOTS];
+
+    /// The slot of `caps`, claiming a free one if there is none yet
+    fn slot(caps: u32) -> Option<&'static Slot> {
+        for slot in &COUNTERS {
+            match slot.caps.load(Ordering::Acquire) {
+                current if current == caps => return Some(slot),
+                FREE => match slot.caps.compare_exchange(
+                    FREE,
+                    caps,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => return Some(slot),
+                    Err(current) if current == caps => return Some(slot),
+                    Err(_) => (),
+                },
+                _ => (),
+            }
+        }
+
+        None
+    }
+
+    pub fn allocated(caps: u32, size: usize, succeeded: bool) {
+        if let Some(slot) = slot(caps) {
+            if succeeded {
+                slot.allocations.fetch_add(1, Ordering::Relaxed);
+                slot.grow(size);
+            } else {
+                slot.failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn deallocated(caps: u32, size: usize) {
+        if let Some(slot) = slot(caps) {
+            slot.deallocations.fetch_add(1, Ordering::Relaxed);
+            slot.shrink(size);
+        }
+    }
+
+    pub fn reallocated(caps: u32, size: usize, new_size: usize, succeeded: bool) {
+        if let Some(slot) = slot(caps) {
+            if !succeeded {
+                slot.failed.fetch_add(1, Ordering::Relaxed);
+            } else if new_size > size {
+                slot.grow(new_size - size);
+            } else {
+                slot.shrink(size - new_size);
+            }
+        }
+    }
+
+    pub fn get(caps: u32) -> Option<AllocCounters> {
+        COUNTERS
+            .iter()
+            .find(|slot| slot.caps.load(Ordering::Acquire) == caps)
+            .map(|slot| AllocCounters {
+                current_bytes: slot.current.load(Ordering::Relaxed),
+                peak_bytes: slot.peak.load(Ordering::Relaxed),
+                allocations: slot.allocations.load(Ordering::Relaxed),
+                deallocations: slot.deallocations.load(Ordering::Relaxed),
+                failed_allocations: slot.failed.load(Ordering::Relaxed),
+            })
+    }
+
+    /// The capabilities counted in slot `index`: `None` past the last slot, and
+    /// `Some(None)` for an unclaimed slot
+    pub fn caps(index: usize) -> Option<Option<u32>> {
+        COUNTERS.get(index).map(|slot| {
+            let caps = slot.caps.load(Ordering::Acquire);
+            (caps != FREE).then_some(caps)
+        })
+    }
 }
@@ -6,30 +6,118 @@
 //! - project_name - from CARGO_PKG_NAME
 //! - time - current build time
 //! - date - current build date
+//!
+//!   Both are taken from the `SOURCE_DATE_EPOCH` environment variable instead, if it is set when
+//!   building `esp-idf-sys`, which makes the description deterministic for reproducible builds.
+//!   Without `SOURCE_DATE_EPOCH`, both are left empty if `CONFIG_APP_REPRODUCIBLE_BUILD` is enabled,
+//!   and both are always left empty if `CONFIG_APP_COMPILE_TIME_DATE` is disabled
 //! - idf_ver - current IDF version from bindings
-//! - app_elf_sha256 - [0; 32]
+//! - app_elf_sha256 - [0; 32]; use `esp-app-desc embed-sha256 <ELF>` (see `tools/esp-app-desc`) to embed
+//!   the SHA256 of the linked ELF file post-link
 //! - min_efuse_blk_rev_full - CONFIG_ESP_EFUSE_BLOCK_REV_MIN_FULL
 //! - secure_version - 0
+//! - mmu_page_size - 0
+//!
+//! The version, the project name, the secure version and the MMU page size can be customized
+//! with keyword arguments, which correspond to the fields of [`EspAppDescOptions`]:
+//! ```ignore
+//! esp_idf_sys::esp_app_desc!(
+//!     version = esp_idf_sys::esp_app_desc_git_version!(),
+//!     project_name = "my-firmware",
+//!     secure_version = 3,
+//! );
+//! ```
+//!
+//! At runtime, the description of the running application - or of the application in another
+//! OTA partition - can be read back with [`AppDescription`].
 //!
-//! If you need a custom definition, don't use the macro but rather - manually define your own
+//! If you need a custom definition beyond that, don't use the macro but rather - manually define your own
 //! static instance of the `esp_app_desc_t` structure in the `.rodata_desc` link section.
 
+/// The customizable parts of the application description defined by the `esp_app_desc!` macro.
+///
+/// Every field can be passed as a keyword argument to the macro; fields not passed
+/// keep the defaults of [`EspAppDescOptions::new`].
+#[derive(Copy, Clone, Debug)]
+pub struct EspAppDescOptions {
+    /// The application version (at most 31 bytes)
+    pub version: &'static str,
+    /// The project name (at most 31 bytes)
+    pub project_name: &'static str,
+    /// The secure version, used by the anti-rollback feature of the bootloader
+    pub secure_version: u32,
+    /// The MMU page size, in log base 2 format (ESP-IDF V5.4+ only)
+    pub mmu_page_size: u8,
+}
+
+impl EspAppDescOptions {
+    /// Create options with the given version and project name, a secure version of 0
+    /// and an MMU page size of 0.
+    ///
+    /// The macro passes the `CARGO_PKG_VERSION` and `CARGO_PKG_NAME` of the invoking crate here.
+    pub const fn new(version: &'static str, project_name: &'static str) -> Self {
+        Self {
+            version,
+            project_name,
+            secure_version: 0,
+            mmu_page_size: 0,
+        }
+    }
+}
+
+/// The build (date, time) of the `SOURCE_DATE_EPOCH` environment variable, if it was set
+/// when building this crate; used by the `esp_app_desc!` macro for reproducible builds.
+#[doc(hidden)]
+pub const SOURCE_DATE_EPOCH_DATE_TIME: Option<(&str, &str)> = match (
+    option_env!("ESP_IDF_SYS_SOURCE_DATE"),
+    option_env!("ESP_IDF_SYS_SOURCE_TIME"),
+) {
+    (Some(date), Some(time)) => Some((date, time)),
+    _ => None,
+};
+
+/// Expands to the `git describe --always --dirty --tags` version of the invoking crate,
+/// for use as the `version` keyword argument of the `esp_app_desc!` macro.
+///
+/// Falls back to `CARGO_PKG_VERSION` if the crate is not in a git repository.
 #[macro_export]
-macro_rules! esp_app_desc {
+macro_rules! esp_app_desc_git_version {
     () => {
-        // For backwards compatibility
-        $crate::esp_app_desc!(false);
-    };
-    {} => {
-        // New way to call the macro
-        $crate::esp_app_desc!(true);
+        $crate::git_version::git_version!(
+            args = ["--always", "--dirty", "--tags"],
+            cargo_prefix = ""
+        )
     };
-    ($fix_date_time_swap: expr) => {
+}
+
+#[macro_export]
+macro_rules! esp_app_desc {
+    (@define $fix_date_time_swap: expr, $options: expr) => {
         #[no_mangle]
         #[used]
         #[link_section = ".rodata_desc"]
         #[allow(non_upper_case_globals)]
         pub static esp_app_desc: $crate::esp_app_desc_t = {
+            const OPTIONS: $crate::EspAppDescOptions = $options;
+
+            // The build (date, time): from `SOURCE_DATE_EPOCH` if set, otherwise the current
+            // time, unless `CONFIG_APP_REPRODUCIBLE_BUILD` is enabled
+            #[cfg(not(esp_idf_app_compile_time_date))]
+            const DATE_TIME: (&str, &str) = ("", "");
+            #[cfg(all(esp_idf_app_compile_time_date, esp_idf_app_reproducible_build))]
+            const DATE_TIME: (&str, &str) = match $crate::SOURCE_DATE_EPOCH_DATE_TIME {
+                Some(date_time) => date_time,
+                None => ("", ""),
+            };
+            #[cfg(all(esp_idf_app_compile_time_date, not(esp_idf_app_reproducible_build)))]
+            const DATE_TIME: (&str, &str) = match $crate::SOURCE_DATE_EPOCH_DATE_TIME {
+                Some(date_time) => date_time,
+                None => (
+                    $crate::build_time::build_time_utc!("%Y-%m-%d"),
+                    $crate::build_time::build_time_utc!("%H:%M:%S"),
+                ),
+            };
+
             const fn str_to_cstr_array<const C: usize>(s: &str) -> [::core::ffi::c_char; C] {
                 let bytes = s.as_bytes();
                 assert!(bytes.len() < C);
@@ -46,32 +134,20 @@ macro_rules! esp_app_desc {
 
             $crate::esp_app_desc_t {
                 magic_word: $crate::ESP_APP_DESC_MAGIC_WORD,
-                secure_version: 0,
+                secure_version: OPTIONS.secure_version,
                 reserv1: [0; 2],
-                version: str_to_cstr_array(env!("CARGO_PKG_VERSION")),
-                project_name: str_to_cstr_array(env!("CARGO_PKG_NAME")),
-                #[cfg(all(esp_idf_app_compile_time_date, not(esp_idf_app_reproducible_build)))]
+                version: str_to_cstr_array(OPTIONS.version),
+                project_name: str_to_cstr_array(OPTIONS.project_name),
                 time: str_to_cstr_array(if $fix_date_time_swap {
-                    $crate::build_time::build_time_utc!("%H:%M:%S")
+                    DATE_TIME.1
                 } else {
-                    $crate::build_time::build_time_utc!("%Y-%m-%d")
+                    DATE_TIME.0
                 }),
-                #[cfg(all(esp_idf_app_compile_time_date, not(esp_idf_app_reproducible_build)))]
                 date: str_to_cstr_array(if $fix_date_time_swap {
-                    $crate::build_time::build_time_utc!("%Y-%m-%d")
+                    DATE_TIME.0
                 } else {
-                    $crate::build_time::build_time_utc!("%H:%M:%S")
+                    DATE_TIME.1
                 }),
-                #[cfg(not(all(
-                    esp_idf_app_compile_time_date,
-                    not(esp_idf_app_reproducible_build)
-                )))]
-                time: [0i8; 16],
-                #[cfg(not(all(
-                    esp_idf_app_compile_time_date,
-                    not(esp_idf_app_reproducible_build)
-                )))]
-                date: [0i8; 16],
                 idf_ver: str_to_cstr_array($crate::const_format::formatcp!(
                     "{}.{}.{}",
                     $crate::ESP_IDF_VERSION_MAJOR,
@@ -92,7 +168,7 @@ macro_rules! esp_app_desc {
                 ))]
                 max_efuse_blk_rev_full: $crate::CONFIG_ESP_EFUSE_BLOCK_REV_MAX_FULL as _,
                 #[cfg(esp_idf_version_at_least_5_4_0)]
-                mmu_page_size: 0,
+                mmu_page_size: OPTIONS.mmu_page_size,
                 #[cfg(esp_idf_version_at_least_5_4_0)]
                 reserv3: [0; 3],
                 #[cfg(esp_idf_version_at_least_5_4_0)]
@@ -112,4 +188,259 @@ macro_rules! esp_app_desc {
             }
         };
     };
+    () => {
+        // For backwards compatibility
+        $crate::esp_app_desc!(false);
+    };
+    {} => {
+        // New way to call the macro
+        $crate::esp_app_desc!(true);
+    };
+    ($($key:ident = $value:expr),+ $(,)?) => {
+        $crate::esp_app_desc!(
+            @define true,
+            $crate::EspAppDescOptions {
+                $($key: $value,)+
+                ..$crate::EspAppDescOptions::new(env!("CARGO_PKG_VERSION"), env!("CARGO_PKG_NAME"))
+            }
+        );
+    };
+    ($fix_date_time_swap: expr) => {
+        $crate::esp_app_desc!(
+            @define $fix_date_time_swap,
+            $crate::EspAppDescOptions::new(env!("CARGO_PKG_VERSION"), env!("CARGO_PKG_NAME"))
+        );
+    };
 }
+
+/// A safe view of an application description (`esp_app_desc_t`).
+///
+/// Use [`AppDescription::running`] for the description of the running application and
+/// [`AppDescription::of_partition`] for the description of the application in an OTA partition.
+#[repr(transparent)]
+pub struct AppDescription(crate::esp_app_desc_t);
+
+impl AppDescription {
+    /// Get the description of the running application.
+    pub fn running() -> &'static Self {
+        // SAFETY: `esp_app_get_description` returns a pointer to the static `esp_app_desc`
+        // description of the application, and `AppDescription` is `repr(transparent)`
+        unsafe { &*(crate::esp_app_get_description() as *const Self) }
+    }
+
+    /// Read the description of the application in the OTA (or factory) partition `partition`.
+    ///
+    /// Fails with `ESP_ERR_NOT_FOUND` if the partition does not hold a valid application.
+    ///
+    /// # Safety
+    ///
+    /// `partition` must be a valid pointer to an application partition, as returned
+    /// e.g. by `esp_ota_get_next_update_partition` or `esp_partition_find_first`.
+    #[cfg(esp_idf_comp_app_update_enabled)]
+    pub unsafe fn of_partition(
+        partition: *const crate::esp_partition_t,
+    ) -> Result<Self, crate::EspError> {
+        let mut desc = core::mem::MaybeUninit::<crate::esp_app_desc_t>::zeroed();
+
+        crate::esp!(crate::esp_ota_get_partition_description(
+            partition,
+            desc.as_mut_ptr()
+        ))?;
+
+        Ok(Self(desc.assume_init()))
+    }
+
+    /// The application version
+    pub fn version(&self) -> &str {
+        c_chars_to_str(&self.0.version)
+    }
+
+    /// The project name
+    pub fn project_name(&self) -> &str {
+        c_chars_to_str(&self.0.project_name)
+    }
+
+    /// The build time; empty if the application was built without `CONFIG_APP_COMPILE_TIME_DATE`
+    pub fn time(&self) -> &str {
+        let (time, date) = self.date_time();
+
+        if time.contains('-') && date.contains(':') {
+            // Defined by the legacy `esp_app_desc!()` form of the macro, which swaps the fields
+            date
+        } else {
+            time
+        }
+    }
+
+    /// The build date; empty if the application was built without `CONFIG_APP_COMPILE_TIME_DATE`
+    pub fn date(&self) -> &str {
+        let (time, date) = self.date_time();
+
+        if time.contains('-') && date.contains(':') {
+            time
+        } else {
+            date
+        }
+    }
+
+    /// The version of ESP-IDF the application was built with
+    pub fn idf_ver(&self) -> &str {
+        c_chars_to_str(&self.0.idf_ver)
+    }
+
+    /// The secure version, used by the anti-rollback feature of the bootloader
+    pub fn secure_version(&self) -> u32 {
+        self.0.secure_version
+    }
+
+    /// The SHA256 of the application ELF file
+    pub fn app_elf_sha256(&self) -> &[u8; 32] {
+        &self.0.app_elf_sha256
+    }
+
+    /// The SHA256 of the application ELF file, as a lowercase hex string
+    pub fn app_elf_sha256_hex(&self) -> AppElfSha256Hex {
+        const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+        let mut hex = [0; 64];
+        for (index, byte) in self.0.app_elf_sha256.iter().enumerate() {
+            hex[index * 2] = DIGITS[(byte >> 4) as usize];
+            hex[index * 2 + 1] = DIGITS[(byte & 0xf) as usize];
+        }
+
+        AppElfSha256Hex(hex)
+    }
+
+    /// The raw application description
+    pub fn raw(&self) -> &crate::esp_app_desc_t {
+        &self.0
+    }
+
+    fn date_time(&self) -> (&str, &str) {
+        (c_chars_to_str(&self.0.time), c_chars_to_str(&self.0.date))
+    }
+}
+
+impl core::fmt::Debug for AppDescription {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AppDescription")
+            .field("version", &self.version())
+            .field("project_name", &self.project_name())
+            .field("time", &self.time())
+            .field("date", &self.date())
+            .field("idf_ver", &self.idf_ver())
+            .field("secure_version", &self.secure_version())
+            .field("app_elf_sha256", &self.app_elf_sha256_hex().as_str())
+            .finish()
+    }
+}
+
+/// The SHA256 of an application ELF file as a lowercase hex string; see
+/// [`AppDescription::app_elf_sha256_hex`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct AppElfSha256Hex([u8; 64]);
+
+impl AppElfSha256Hex {
+    /// The 64 lowercase hex digits of the SHA256
+    pub fn as_str(&self) -> &str {
+        // SAFETY: Only ever contains ASCII hex digits
+        unsafe { core::str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+impl core::fmt::Display for AppElfSha256Hex {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl core::fmt::Debug for AppElfSha256Hex {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// Convert a NUL-terminated `c_char` array field to a `&str`, up to the first NUL
+/// (or the whole array, if not NUL-terminated) and the first invalid UTF-8 sequence.
+fn c_chars_to_str(chars: &[core::ffi::c_char]) -> &str {
+    // SAFETY: `c_char` and `u8` have the same layout
+    let bytes = unsafe { core::slice::from_raw_parts(chars.as_ptr() as *const u8, chars.len()) };
+    let bytes = bytes
+        .iter()
+        .position(|b| *b == 0)
+        .map_or(bytes, |len| &bytes[..len]);
+
+    match core::str::from_utf8(bytes) {
+        Ok(s) => s,
+        // SAFETY: Valid up to this point
+        Err(err) => unsafe { core::str::from_utf8_unchecked(&bytes[..err.valid_up_to()]) },
+    }
+}
+
+// The layout of `esp_app_desc_t` assumed by the host-side tooling in `tools/esp-app-desc`
+#[path = "app_desc_layout.rs"]
+mod layout;
+
+const _: () = {
+    use core::mem::{offset_of, size_of};
+
+    use crate::{
+        esp_app_desc_t, ESP_IDF_VERSION_MAJOR, ESP_IDF_VERSION_MINOR, ESP_IDF_VERSION_PATCH,
+    };
+
+    assert!(size_of::<esp_app_desc_t>() == layout::SIZE);
+    assert!(offset_of!(esp_app_desc_t, magic_word) == layout::MAGIC_WORD_OFFSET);
+    assert!(crate::ESP_APP_DESC_MAGIC_WORD == layout::MAGIC_WORD);
+    assert!(offset_of!(esp_app_desc_t, secure_version) == layout::SECURE_VERSION_OFFSET);
+    assert!(offset_of!(esp_app_desc_t, version) == layout::VERSION_OFFSET);
+    assert!(offset_of!(esp_app_desc_t, project_name) == layout::PROJECT_NAME_OFFSET);
+    assert!(offset_of!(esp_app_desc_t, time) == layout::TIME_OFFSET);
+    assert!(offset_of!(esp_app_desc_t, date) == layout::DATE_OFFSET);
+    assert!(offset_of!(esp_app_desc_t, idf_ver) == layout::IDF_VER_OFFSET);
+    assert!(offset_of!(esp_app_desc_t, app_elf_sha256) == layout::APP_ELF_SHA256_OFFSET);
+
+    let efuse_blk_rev_full = layout::has_efuse_blk_rev_full(
+        ESP_IDF_VERSION_MAJOR,
+        ESP_IDF_VERSION_MINOR,
+        ESP_IDF_VERSION_PATCH,
+    );
+
+    #[cfg(any(
+        esp_idf_version_patch_at_least_5_1_7,
+        esp_idf_version_patch_at_least_5_2_3,
+        esp_idf_version_at_least_5_3_2,
+    ))]
+    {
+        assert!(efuse_blk_rev_full);
+        assert!(
+            offset_of!(esp_app_desc_t, min_efuse_blk_rev_full)
+                == layout::MIN_EFUSE_BLK_REV_FULL_OFFSET
+        );
+        assert!(
+            offset_of!(esp_app_desc_t, max_efuse_blk_rev_full)
+                == layout::MAX_EFUSE_BLK_REV_FULL_OFFSET
+        );
+    }
+
+    #[cfg(not(any(
+        esp_idf_version_patch_at_least_5_1_7,
+        esp_idf_version_patch_at_least_5_2_3,
+        esp_idf_version_at_least_5_3_2,
+    )))]
+    assert!(!efuse_blk_rev_full);
+
+    let mmu_page_size = layout::has_mmu_page_size(
+        ESP_IDF_VERSION_MAJOR,
+        ESP_IDF_VERSION_MINOR,
+        ESP_IDF_VERSION_PATCH,
+    );
+
+    #[cfg(esp_idf_version_at_least_5_4_0)]
+    {
+        assert!(mmu_page_size);
+        assert!(offset_of!(esp_app_desc_t, mmu_page_size) == layout::MMU_PAGE_SIZE_OFFSET);
+    }
+
+    #[cfg(not(esp_idf_version_at_least_5_4_0))]
+    assert!(!mmu_page_size);
+};
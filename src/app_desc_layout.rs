@@ -0,0 +1,80 @@
+//! The binary layout of the ESP-IDF `esp_app_desc_t` structure, as placed by the
+//! `esp_app_desc!` macro at the start of the `.rodata_desc` link section.
+//!
+//! This module is shared - via `#[path]` - with the host-side tooling in `tools/esp-app-desc`,
+//! which cannot use the bindings. The offsets are checked against the bindings at compile time.
+
+#![allow(dead_code)]
+
+/// The size of the `esp_app_desc_t` structure
+pub const SIZE: usize = 256;
+
+/// The offset of the `magic_word` field (`u32`)
+pub const MAGIC_WORD_OFFSET: usize = 0;
+/// The value of the `magic_word` field (`ESP_APP_DESC_MAGIC_WORD`)
+pub const MAGIC_WORD: u32 = 0xABCD5432;
+
+/// The offset of the `secure_version` field (`u32`)
+pub const SECURE_VERSION_OFFSET: usize = 4;
+
+/// The offset of the `version` field (`[c_char; 32]`)
+pub const VERSION_OFFSET: usize = 16;
+/// The length of the `version` field
+pub const VERSION_LEN: usize = 32;
+
+/// The offset of the `project_name` field (`[c_char; 32]`)
+pub const PROJECT_NAME_OFFSET: usize = 48;
+/// The length of the `project_name` field
+pub const PROJECT_NAME_LEN: usize = 32;
+
+/// The offset of the `time` field (`[c_char; 16]`)
+pub const TIME_OFFSET: usize = 80;
+/// The length of the `time` field
+pub const TIME_LEN: usize = 16;
+
+/// The offset of the `date` field (`[c_char; 16]`)
+pub const DATE_OFFSET: usize = 96;
+/// The length of the `date` field
+pub const DATE_LEN: usize = 16;
+
+/// The offset of the `idf_ver` field (`[c_char; 32]`)
+pub const IDF_VER_OFFSET: usize = 112;
+/// The length of the `idf_ver` field
+pub const IDF_VER_LEN: usize = 32;
+
+/// The offset of the `app_elf_sha256` field (`[u8; 32]`)
+pub const APP_ELF_SHA256_OFFSET: usize = 144;
+/// The length of the `app_elf_sha256` field
+pub const APP_ELF_SHA256_LEN: usize = 32;
+
+/// The offset of the `min_efuse_blk_rev_full` field (`u16`), if present;
+/// see [`has_efuse_blk_rev_full`]
+pub const MIN_EFUSE_BLK_REV_FULL_OFFSET: usize = 176;
+/// The offset of the `max_efuse_blk_rev_full` field (`u16`), if present;
+/// see [`has_efuse_blk_rev_full`]
+pub const MAX_EFUSE_BLK_REV_FULL_OFFSET: usize = 178;
+
+/// The offset of the `mmu_page_size` field (`u8`), if present; see [`has_mmu_page_size`]
+pub const MMU_PAGE_SIZE_OFFSET: usize = 180;
+
+/// The name of the link section holding the structure
+pub const LINK_SECTION: &str = ".rodata_desc";
+
+/// Return `true` if the structure of the given ESP-IDF version has the
+/// `min_efuse_blk_rev_full` and `max_efuse_blk_rev_full` fields
+/// (ESP-IDF V5.1.7+, V5.2.3+, V5.3.2+ and V5.4+).
+pub const fn has_efuse_blk_rev_full(major: u32, minor: u32, patch: u32) -> bool {
+    match (major, minor) {
+        (5, 1) => patch >= 7,
+        (5, 2) => patch >= 3,
+        (5, 3) => patch >= 2,
+        (5, minor) => minor >= 4,
+        (major, _) => major >= 6,
+    }
+}
+
+/// Return `true` if the structure of the given ESP-IDF version has the
+/// `mmu_page_size` field (ESP-IDF V5.4+).
+pub const fn has_mmu_page_size(major: u32, minor: u32, _patch: u32) -> bool {
+    major > 5 || (major == 5 && minor >= 4)
+}
@@ -0,0 +1,146 @@
+use core::{ffi, fmt, num::NonZeroI32, str};
+
+use crate::{strerror, EIO};
+
+/// A wrapped `errno` value, as reported by the POSIX/VFS functions of ESP-IDF
+/// (file, socket, `select` etc.).
+///
+/// These functions usually return `-1` on failure and report the actual error
+/// by setting the thread-local `errno` of the C library; see [`errno!`] and
+/// [`errno_result!`].
+#[repr(transparent)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct EspErrno(NonZeroI32);
+
+impl EspErrno {
+    /// Wrap an `errno` value, return [`Some`] if `errno` is **not** 0.
+    pub const fn from(errno: ffi::c_int) -> Option<Self> {
+        match NonZeroI32::new(errno) {
+            None => None,
+            Some(errno) => Some(Self(errno)),
+        }
+    }
+
+    /// Wrap a [`NonZeroI32`] `errno` value.
+    pub const fn from_non_zero(errno: NonZeroI32) -> Self {
+        Self(errno)
+    }
+
+    /// Get the current value of the thread-local `errno`, return [`Some`] if it is
+    /// **not** 0.
+    pub fn last() -> Option<Self> {
+        Self::from(unsafe { *errno_location() })
+    }
+
+    /// Convert the return value `ret` of a POSIX function into a [`Result`] with
+    /// `Ok(value)` if no error occurred.
+    ///
+    /// If `ret` is `-1`, return [`Err`] of the current thread-local `errno`, otherwise
+    /// return [`Ok`] of `value`. Should the function have failed without setting `errno`,
+    /// `EIO` is reported.
+    pub fn check_and_return<R, T>(ret: R, value: T) -> Result<T, Self>
+    where
+        R: PartialEq + From<i8>,
+    {
+        if ret == R::from(-1) {
+            Err(Self::last().unwrap_or_else(|| Self::from(EIO as _).unwrap()))
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// Convert the return value `ret` of a POSIX function into a [`Result`] with
+    /// `Ok(ret)` if no error occurred.
+    ///
+    /// See [`EspErrno::check_and_return`].
+    pub fn convert<R>(ret: R) -> Result<R, Self>
+    where
+        R: PartialEq + From<i8> + Copy,
+    {
+        Self::check_and_return(ret, ret)
+    }
+
+    /// Panic with a specific error message of the contained `errno` value.
+    #[track_caller]
+    pub fn panic(&self) {
+        panic!("ESP-IDF ERRNO: {self}");
+    }
+
+    /// Get the wrapped `errno` value.
+    pub fn code(&self) -> ffi::c_int {
+        self.0.get()
+    }
+}
+
+impl core::error::Error for EspErrno {}
+
+impl fmt::Display for EspErrno {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        unsafe {
+            let s = ffi::CStr::from_ptr(strerror(self.code()));
+            core::fmt::Display::fmt(&str::from_utf8_unchecked(s.to_bytes()), f)
+        }
+    }
+}
+
+impl fmt::Debug for EspErrno {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (errno {})", self, self.code())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<EspErrno> for std::io::Error {
+    fn from(errno: EspErrno) -> Self {
+        std::io::Error::from_raw_os_error(errno.code())
+    }
+}
+
+/// Set the thread-local `errno` of the C library.
+#[allow(dead_code)]
+pub(crate) fn set_errno(errno: ffi::c_int) {
+    unsafe {
+        *errno_location() = errno;
+    }
+}
+
+/// Get a pointer to the thread-local `errno` of the C library.
+#[cfg(not(esp_idf_libc_picolibc))]
+unsafe fn errno_location() -> *mut ffi::c_int {
+    // Newlib keeps `errno` in the reentrancy structure of the current task
+    &mut (*crate::__getreent())._errno
+}
+
+/// Get a pointer to the thread-local `errno` of the C library.
+#[cfg(esp_idf_libc_picolibc)]
+unsafe fn errno_location() -> *mut ffi::c_int {
+    extern "C" {
+        // Picolibc keeps `errno` in a `__thread` variable, which cannot be referenced
+        // from (stable) Rust, so go through the accessor ESP-IDF provides for compatibility
+        // with Newlib - the same one the Rust Standard Library uses
+        fn __errno() -> *mut ffi::c_int;
+    }
+
+    __errno()
+}
+
+/// Convert the return value of a POSIX function into a [`Result<R, EspErrno>`](Result),
+/// where `Ok` carries the return value itself.
+///
+/// See [`EspErrno::convert`].
+#[macro_export]
+macro_rules! errno {
+    ($ret:expr) => {{
+        $crate::EspErrno::convert($ret)
+    }};
+}
+
+/// Convert the return value of a POSIX function into a [`Result<T, EspErrno>`](Result).
+///
+/// See [`EspErrno::check_and_return`].
+#[macro_export]
+macro_rules! errno_result {
+    ($ret:expr, $value:expr) => {{
+        $crate::EspErrno::check_and_return($ret, $value)
+    }};
+}
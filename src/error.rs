@@ -70,6 +70,262 @@ impl EspError {
     pub fn code(&self) -> esp_err_t {
         self.0.get()
     }
+
+    /// Get the ESP-IDF component owning the error code, if the code is a known
+    /// `ESP_ERR_*` constant.
+    ///
+    /// The generic error codes (`ESP_ERR_NO_MEM`, `ESP_ERR_INVALID_ARG` etc.) belong
+    /// to the `common` component.
+    pub fn component(&self) -> Option<EspErrorComponent> {
+        catalogue::lookup(self.code()).map(|(component, _)| component)
+    }
+
+    /// Get the coarse category of the error.
+    ///
+    /// Codes which are not known `ESP_ERR_*` constants (including [`ESP_FAIL`](crate::ESP_FAIL))
+    /// are categorized as [`EspErrorKind::Other`].
+    pub fn kind(&self) -> EspErrorKind {
+        catalogue::lookup(self.code())
+            .map(|(_, kind)| kind)
+            .unwrap_or(EspErrorKind::Other)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<EspError> for std::io::Error {
+    /// Convert the error into an [`std::io::Error`] of the closest matching [`std::io::ErrorKind`].
+    ///
+    /// The [`EspError`] is retained as the inner error and can be recovered with
+    /// [`EspError::try_from`].
+    fn from(error: EspError) -> Self {
+        std::io::Error::new(io_error_kind(error), error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<std::io::Error> for EspError {
+    type Error = std::io::Error;
+
+    /// Recover the [`EspError`] from an [`std::io::Error`] produced by converting it
+    /// (or an [`EspErrorWithLocation`]) with its `From` conversion; any other error is
+    /// returned as-is.
+    fn try_from(error: std::io::Error) -> Result<Self, Self::Error> {
+        let esp_error = error.get_ref().and_then(|inner| {
+            let esp_error = inner.downcast_ref::<EspError>().copied();
+
+            #[cfg(feature = "error_location")]
+            let esp_error = esp_error.or_else(|| {
+                inner
+                    .downcast_ref::<EspErrorWithLocation>()
+                    .map(|located| located.error)
+            });
+
+            esp_error
+        });
+
+        match esp_error {
+            Some(esp_error) => Ok(esp_error),
+            None => Err(error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn io_error_kind(error: EspError) -> std::io::ErrorKind {
+    use std::io::ErrorKind;
+
+    match error.code() {
+        crate::ESP_ERR_NOT_ALLOWED => ErrorKind::PermissionDenied,
+        crate::ESP_ERR_INVALID_RESPONSE | crate::ESP_ERR_INVALID_CRC => ErrorKind::InvalidData,
+        _ => match error.kind() {
+            EspErrorKind::Timeout => ErrorKind::TimedOut,
+            EspErrorKind::InvalidArgument => ErrorKind::InvalidInput,
+            EspErrorKind::NoMemory => ErrorKind::OutOfMemory,
+            EspErrorKind::NotFound => ErrorKind::NotFound,
+            EspErrorKind::NotSupported => ErrorKind::Unsupported,
+            _ => ErrorKind::Other,
+        },
+    }
+}
+
+/// An [`EspError`] along with the source location and the expression which produced it.
+///
+/// This is what the [`esp_loc!`] and [`esp_result_loc!`] macros of the `error_location`
+/// feature return. It dereferences to - and converts into - the wrapped [`EspError`],
+/// so `?` keeps working in functions returning `Result<_, EspError>`.
+#[cfg(feature = "error_location")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct EspErrorWithLocation {
+    error: EspError,
+    file: &'static str,
+    line: u32,
+    expr: &'static str,
+}
+
+#[cfg(feature = "error_location")]
+impl EspErrorWithLocation {
+    /// Wrap `error`, which was produced by the expression `expr` at `file`:`line`.
+    pub const fn new(error: EspError, file: &'static str, line: u32, expr: &'static str) -> Self {
+        Self {
+            error,
+            file,
+            line,
+            expr,
+        }
+    }
+
+    /// Get the wrapped [`EspError`].
+    pub const fn error(&self) -> EspError {
+        self.error
+    }
+
+    /// Get the source file of the expression which produced the error.
+    pub const fn file(&self) -> &'static str {
+        self.file
+    }
+
+    /// Get the source line of the expression which produced the error.
+    pub const fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Get the stringified expression which produced the error.
+    pub const fn expr(&self) -> &'static str {
+        self.expr
+    }
+
+    /// Panic with a specific error message of the contained [`esp_err_t`] and its location.
+    #[track_caller]
+    pub fn panic(&self) {
+        panic!("ESP-IDF ERROR: {self}");
+    }
+}
+
+#[cfg(feature = "error_location")]
+impl core::ops::Deref for EspErrorWithLocation {
+    type Target = EspError;
+
+    fn deref(&self) -> &Self::Target {
+        &self.error
+    }
+}
+
+#[cfg(feature = "error_location")]
+impl From<EspErrorWithLocation> for EspError {
+    fn from(error: EspErrorWithLocation) -> Self {
+        error.error
+    }
+}
+
+#[cfg(feature = "error_location")]
+impl core::error::Error for EspErrorWithLocation {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(feature = "error_location")]
+impl fmt::Display for EspErrorWithLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}:{} ({})",
+            self.error, self.file, self.line, self.expr
+        )
+    }
+}
+
+#[cfg(feature = "error_location")]
+impl fmt::Debug for EspErrorWithLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (error code {}) at {}:{} ({})",
+            self.error,
+            self.error.code(),
+            self.file,
+            self.line,
+            self.expr
+        )
+    }
+}
+
+#[cfg(all(feature = "std", feature = "error_location"))]
+impl From<EspErrorWithLocation> for std::io::Error {
+    fn from(error: EspErrorWithLocation) -> Self {
+        std::io::Error::new(io_error_kind(error.error), error)
+    }
+}
+
+/// The ESP-IDF component owning an [`EspError`] code, as returned by [`EspError::component`].
+///
+/// The components are derived from the `ESP_ERR_<COMPONENT>_BASE` constants of the bindings.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct EspErrorComponent {
+    name: &'static str,
+    base: esp_err_t,
+}
+
+impl EspErrorComponent {
+    /// Get the name of the component, e.g. `wifi`, `nvs` or `common`.
+    ///
+    /// This is the lowercased `<COMPONENT>` part of the `ESP_ERR_<COMPONENT>_BASE` constant.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the base of the error code range of the component.
+    pub const fn base(&self) -> esp_err_t {
+        self.base
+    }
+}
+
+impl fmt::Display for EspErrorComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// A coarse category of an [`EspError`], as returned by [`EspError::kind`].
+///
+/// The category is derived from the name of the `ESP_ERR_*` constant of the error code.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum EspErrorKind {
+    /// The operation timed out (e.g. `ESP_ERR_TIMEOUT`)
+    Timeout,
+    /// An argument was invalid (e.g. `ESP_ERR_INVALID_ARG`, `ESP_ERR_INVALID_SIZE`)
+    InvalidArgument,
+    /// The operation is not valid in the current state (e.g. `ESP_ERR_INVALID_STATE`, `ESP_ERR_WIFI_NOT_INIT`)
+    InvalidState,
+    /// Out of memory (e.g. `ESP_ERR_NO_MEM`)
+    NoMemory,
+    /// The requested resource was not found (e.g. `ESP_ERR_NOT_FOUND`, `ESP_ERR_NVS_NOT_FOUND`)
+    NotFound,
+    /// The operation is not supported (e.g. `ESP_ERR_NOT_SUPPORTED`)
+    NotSupported,
+    /// The resource is busy, or an operation is already in progress
+    Busy,
+    /// Any other error
+    Other,
+}
+
+mod catalogue {
+    use super::{EspErrorComponent, EspErrorKind};
+    use crate::esp_err_t;
+
+    // Generated by the build script from the `ESP_ERR_*` constants of the bindings
+    include!(concat!(env!("OUT_DIR"), "/esp_err_catalogue.rs"));
+
+    pub(super) fn lookup(code: esp_err_t) -> Option<(EspErrorComponent, EspErrorKind)> {
+        let index = ERRORS
+            .binary_search_by_key(&code, |(code, _, _)| *code)
+            .ok()?;
+        let (_, component, kind) = ERRORS[index];
+        let (name, base) = COMPONENTS[component as usize];
+
+        Some((EspErrorComponent { name, base }, kind))
+    }
 }
 
 impl core::error::Error for EspError {}
@@ -109,9 +365,49 @@ macro_rules! esp_result {
     }};
 }
 
+/// Like [`esp!`], but convert an [`esp_err_t`] into a [`Result<(), EspErrorWithLocation>`](Result),
+/// capturing the source location and the expression of a failing call.
+///
+/// See [`EspError::convert`].
+#[cfg(feature = "error_location")]
+#[macro_export]
+macro_rules! esp_loc {
+    ($err:expr) => {{
+        $crate::EspError::convert($err as $crate::esp_err_t).map_err(|error| {
+            $crate::EspErrorWithLocation::new(
+                error,
+                ::core::file!(),
+                ::core::line!(),
+                ::core::stringify!($err),
+            )
+        })
+    }};
+}
+
+/// Like [`esp_result!`], but convert an [`esp_err_t`] into a
+/// [`Result<T, EspErrorWithLocation>`](Result), capturing the source location and the
+/// expression of a failing call.
+///
+/// See [`EspError::check_and_return`].
+#[cfg(feature = "error_location")]
+#[macro_export]
+macro_rules! esp_result_loc {
+    ($err:expr, $value:expr) => {{
+        $crate::EspError::check_and_return($err as $crate::esp_err_t, $value).map_err(|error| {
+            $crate::EspErrorWithLocation::new(
+                error,
+                ::core::file!(),
+                ::core::line!(),
+                ::core::stringify!($err),
+            )
+        })
+    }};
+}
+
 /// Panic with an error-specific message if `err` is not [`ESP_OK`].
 ///
 /// See [`EspError::from`] and [`EspError::panic`].
+#[cfg(not(feature = "error_location"))]
 #[macro_export]
 macro_rules! esp_nofail {
     ($err:expr) => {{
@@ -122,3 +418,25 @@ macro_rules! esp_nofail {
         }
     }};
 }
+
+/// Panic with an error-specific message, including the failing expression, if `err` is not
+/// [`ESP_OK`].
+///
+/// See [`EspError::from`] and [`EspErrorWithLocation::panic`].
+#[cfg(feature = "error_location")]
+#[macro_export]
+macro_rules! esp_nofail {
+    ($err:expr) => {{
+        if let ::core::option::Option::Some(error) =
+            $crate::EspError::from($err as $crate::esp_err_t)
+        {
+            $crate::EspErrorWithLocation::new(
+                error,
+                ::core::file!(),
+                ::core::line!(),
+                ::core::stringify!($err),
+            )
+            .panic();
+        }
+    }};
+}
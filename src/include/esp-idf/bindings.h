@@ -18,6 +18,12 @@
 #include "esp_mac.h"
 #include "esp_freertos_hooks.h"
 
+#if ESP_IDF_VERSION_MAJOR > 4
+#ifdef ESP_IDF_COMP_ESP_APP_FORMAT_ENABLED
+#include "esp_app_desc.h"
+#endif
+#endif
+
 #include "freertos/FreeRTOS.h"
 #include "freertos/atomic.h"
 #include "freertos/event_groups.h"
@@ -673,6 +679,9 @@
 #include "esp_pthread.h"
 #endif
 
+// For `struct tms`, otherwise only declared
+#include "sys/times.h"
+
 #ifdef ESP_IDF_COMP_USB_ENABLED
 #ifdef CONFIG_USB_OTG_SUPPORTED
 #include "usb/usb_host.h"
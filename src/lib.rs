@@ -10,12 +10,27 @@
     all(not(feature = "std"), feature = "alloc_handler"),
     feature(alloc_error_handler)
 )]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
 #![allow(unknown_lints)]
 #![allow(renamed_and_removed_lints)]
 #![allow(unexpected_cfgs)]
 
+pub use alloc::*;
 pub use bindings::*;
+pub use errno::*;
 pub use error::*;
+pub use pre_main::PreMain;
+
+pub use esp_idf_sys_macros::pre_main;
+
+#[cfg(esp_idf_version_at_least_5_1_0)]
+pub use app_desc::*;
+#[cfg(all(not(feature = "std"), feature = "panic_handler"))]
+pub use panic::*;
+#[cfg(any(feature = "binstart", feature = "libstart"))]
+pub use start::main_exit::{main_exit_action, set_main_exit_action, MainExitAction};
+#[cfg(all(esp_idf_sys_main_task, any(feature = "binstart", feature = "libstart")))]
+pub use start::main_task::{MAIN_TASK_CORE, MAIN_TASK_PRIORITY, MAIN_TASK_STACK_SIZE};
 
 // Don't use esp_idf_soc_pcnt_supported; that's only on ESP-IDF v5.x+.
 // pcnt_unit_t and friends are only needed for the legacy PCNT API (removed in v6.0).
@@ -30,7 +45,11 @@ pub use build_time;
 #[doc(hidden)]
 pub use const_format;
 #[doc(hidden)]
+pub use git_version;
+#[doc(hidden)]
 pub use patches::PatchesRef;
+#[cfg(feature = "std")]
+pub use patches::{register_lstat, unregister_lstat, LstatFn};
 
 #[cfg(feature = "std")]
 #[allow(unused_imports)]
@@ -45,7 +64,10 @@ extern crate alloc;
 mod alloc;
 #[cfg(esp_idf_version_at_least_5_1_0)]
 mod app_desc;
+mod errno;
 mod error;
+#[cfg(feature = "log")]
+pub mod logger;
 mod panic;
 mod patches;
 #[cfg(all(
@@ -53,17 +75,26 @@ mod patches;
     any(esp32, esp32s2, esp32s3, esp32c5, esp32c6, esp32h2, esp32p4)
 ))]
 mod pcnt;
+mod pre_main;
 
 mod checks;
 mod start;
 mod stdio;
+mod vprintf;
 
-pub use stdio::restore_posix_stdio_fds;
+pub use stdio::*;
 
-/// A hack to make sure that a few patches to the ESP-IDF which are implemented in Rust
-/// are linked to the final executable
+/// Makes sure that the patches to the ESP-IDF which are implemented in Rust are linked
+/// to the final executable
 ///
-/// Call this function once at the beginning of your main function
+/// With the `native` builder, the build script passes the symbols of the patches to the
+/// linker as `--undefined` arguments, along with the other linker arguments it propagates,
+/// so calling this function is not necessary. With the `pio` builder, or when building as
+/// a component of an ESP-IDF CMake project, the linker arguments are controlled by the
+/// build of ESP-IDF instead, and the patches are only linked if something references
+/// them - which this function does. So it is not a no-op: call it once at the beginning
+/// of your main function with these builds, unless the `binstart` or `libstart` feature
+/// is enabled, whose `app_main` calls it.
 pub fn link_patches() -> PatchesRef {
     patches::link_patches()
 }
@@ -0,0 +1,461 @@
+//! A bridge between the ESP-IDF logging and the [`log`](::log) crate:
+//! - [`EspLogger`] - a [`Log`] implementation writing the records of the Rust code
+//!   through `esp_log_write`, so that they end up wherever the output of the ESP-IDF
+//!   logging goes, filtered by the same per-tag levels;
+//! - [`capture_esp_log`] - the reverse direction, passing the output of the C
+//!   components (`ESP_LOGI` etc.) as records to the Rust logger.
+//!
+//! The `target` of a record becomes the tag of the ESP-IDF log message and vice versa.
+
+use core::cell::UnsafeCell;
+use core::ffi::{c_char, c_int, c_void, CStr};
+use core::fmt::{self, Write};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, Ordering};
+
+use ::log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::vprintf::{esp_log_set_vprintf, vsnprintf, VaList, VaListCopy, VprintfLike};
+use crate::*;
+
+/// The maximum length of a tag; longer `target`s are truncated
+pub const LOG_TAG_MAX_LEN: usize = 31;
+
+/// The maximum length of a log message passed on by [`EspLogger`] in a single
+/// `esp_log_write` call; longer messages are written in several pieces
+pub const LOG_LINE_MAX_LEN: usize = 255;
+
+/// The `esp_log_write` format of the messages written by [`EspLogger`], by which
+/// [`capture_esp_log`] recognizes - and does not capture again - these messages
+static RUST_FORMAT: &CStr = c"%.*s";
+
+/// The tag of the records of targets which do not fit into the [`TAGS`] table
+static FALLBACK_TAG: &CStr = c"rust";
+
+/// The ESP-IDF logger, writing the records of the [`log`](::log) crate through
+/// `esp_log_write`.
+///
+/// Records are filtered by the ESP-IDF log level of their target, as set with
+/// `esp_log_level_set` or [`EspLogger::set_target_level`] (ESP-IDF v5.0+), and
+/// printed in the format of the ESP-IDF logging (`I (1234) target: message`).
+///
+/// As ESP-IDF keeps pointers to the tags it has seen, the targets are interned
+/// in a fixed-size table of up to 64 entries, truncated to [`LOG_TAG_MAX_LEN`]
+/// bytes. Should a program use more targets, the records of the excess targets
+/// are filtered by the level of the `rust` tag.
+pub struct EspLogger;
+
+/// The [`EspLogger`] instance to pass to [`log::set_logger`](::log::set_logger)
+pub static LOGGER: EspLogger = EspLogger;
+
+impl EspLogger {
+    /// Installs [`LOGGER`] as the logger of the [`log`](::log) crate and sets its
+    /// maximum level to `CONFIG_LOG_MAXIMUM_LEVEL`, i.e. to the most verbose level
+    /// the ESP-IDF logging can be configured to at runtime.
+    pub fn initialize_default() -> Result<(), ::log::SetLoggerError> {
+        ::log::set_logger(&LOGGER)?;
+        ::log::set_max_level(max_level());
+
+        Ok(())
+    }
+
+    /// Sets the ESP-IDF log level of `target` with `esp_log_level_set`; `"*"`
+    /// sets the default level of all tags.
+    ///
+    /// Note that records more verbose than the maximum level of the [`log`](::log)
+    /// crate are dropped before reaching the logger, whatever the level of their target.
+    pub fn set_target_level(&self, target: &str, level: LevelFilter) -> Result<(), EspError> {
+        let tag = if target == "*" {
+            c"*"
+        } else {
+            tag(target).ok_or(EspError::from_infallible::<ESP_ERR_NO_MEM>())?
+        };
+
+        unsafe {
+            esp_log_level_set(tag.as_ptr(), to_esp_log_level(level));
+        }
+
+        Ok(())
+    }
+
+    fn enabled_for(&self, tag: &CStr, level: Level) -> bool {
+        to_esp_log_level(level.to_level_filter()) <= tag_level(tag)
+    }
+}
+
+impl Log for EspLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.enabled_for(
+            tag(metadata.target()).unwrap_or(FALLBACK_TAG),
+            metadata.level(),
+        )
+    }
+
+    fn log(&self, record: &Record) {
+        let tag = tag(record.target()).unwrap_or(FALLBACK_TAG);
+
+        if !self.enabled_for(tag, record.level()) {
+            return;
+        }
+
+        let (color, letter) = match record.level() {
+            Level::Error => (color::ERROR, 'E'),
+            Level::Warn => (color::WARN, 'W'),
+            Level::Info => (color::INFO, 'I'),
+            Level::Debug => ("", 'D'),
+            Level::Trace => ("", 'V'),
+        };
+
+        let mut line = Line::new(to_esp_log_level(record.level().to_level_filter()), tag);
+
+        let _ = writeln!(
+            line,
+            "{color}{letter} ({timestamp}) {target}: {args}{reset}",
+            timestamp = unsafe { esp_log_timestamp() },
+            target = record.target(),
+            args = record.args(),
+            reset = if color.is_empty() { "" } else { color::RESET },
+        );
+
+        line.flush();
+    }
+
+    fn flush(&self) {}
+}
+
+/// Converts a [`LevelFilter`] into the corresponding ESP-IDF log level;
+/// [`LevelFilter::Trace`] corresponds to `ESP_LOG_VERBOSE`.
+pub fn to_esp_log_level(level: LevelFilter) -> esp_log_level_t {
+    match level {
+        LevelFilter::Off => esp_log_level_t_ESP_LOG_NONE,
+        LevelFilter::Error => esp_log_level_t_ESP_LOG_ERROR,
+        LevelFilter::Warn => esp_log_level_t_ESP_LOG_WARN,
+        LevelFilter::Info => esp_log_level_t_ESP_LOG_INFO,
+        LevelFilter::Debug => esp_log_level_t_ESP_LOG_DEBUG,
+        LevelFilter::Trace => esp_log_level_t_ESP_LOG_VERBOSE,
+    }
+}
+
+/// Converts an ESP-IDF log level into the corresponding [`LevelFilter`];
+/// `ESP_LOG_VERBOSE` - and anything more verbose - corresponds to
+/// [`LevelFilter::Trace`].
+#[allow(non_upper_case_globals)]
+pub fn from_esp_log_level(level: esp_log_level_t) -> LevelFilter {
+    match level {
+        esp_log_level_t_ESP_LOG_NONE => LevelFilter::Off,
+        esp_log_level_t_ESP_LOG_ERROR => LevelFilter::Error,
+        esp_log_level_t_ESP_LOG_WARN => LevelFilter::Warn,
+        esp_log_level_t_ESP_LOG_INFO => LevelFilter::Info,
+        esp_log_level_t_ESP_LOG_DEBUG => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+fn max_level() -> LevelFilter {
+    from_esp_log_level(CONFIG_LOG_MAXIMUM_LEVEL as _)
+}
+
+/// The ESP-IDF log level of `tag`
+#[cfg(esp_idf_version_at_least_5_0_0)]
+fn tag_level(tag: &CStr) -> esp_log_level_t {
+    unsafe { esp_log_level_get(tag.as_ptr()) }
+}
+
+/// The ESP-IDF log level of `tag`
+///
+/// `esp_log_level_get` is not available before ESP-IDF v5.0, so leave the
+/// filtering to `esp_log_write`
+#[cfg(not(esp_idf_version_at_least_5_0_0))]
+fn tag_level(_tag: &CStr) -> esp_log_level_t {
+    CONFIG_LOG_MAXIMUM_LEVEL as _
+}
+
+#[cfg(esp_idf_log_colors)]
+mod color {
+    pub(super) const ERROR: &str = "\x1b[0;31m";
+    pub(super) const WARN: &str = "\x1b[0;33m";
+    pub(super) const INFO: &str = "\x1b[0;32m";
+    pub(super) const RESET: &str = "\x1b[0m";
+}
+
+#[cfg(not(esp_idf_log_colors))]
+mod color {
+    pub(super) const ERROR: &str = "";
+    pub(super) const WARN: &str = "";
+    pub(super) const INFO: &str = "";
+    pub(super) const RESET: &str = "";
+}
+
+/// A log message being formatted, written through `esp_log_write` whenever
+/// [`LOG_LINE_MAX_LEN`] bytes are buffered, and when flushed
+struct Line<'a> {
+    level: esp_log_level_t,
+    tag: &'a CStr,
+    buf: [u8; LOG_LINE_MAX_LEN],
+    len: usize,
+}
+
+impl<'a> Line<'a> {
+    const fn new(level: esp_log_level_t, tag: &'a CStr) -> Self {
+        Self {
+            level,
+            tag,
+            buf: [0; LOG_LINE_MAX_LEN],
+            len: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                esp_log_write(
+                    self.level,
+                    self.tag.as_ptr(),
+                    RUST_FORMAT.as_ptr(),
+                    self.len as c_int,
+                    self.buf.as_ptr(),
+                );
+            }
+
+            self.len = 0;
+        }
+    }
+}
+
+impl fmt::Write for Line<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut s = s.as_bytes();
+
+        while !s.is_empty() {
+            if self.len == self.buf.len() {
+                self.flush();
+            }
+
+            let len = s.len().min(self.buf.len() - self.len);
+
+            self.buf[self.len..self.len + len].copy_from_slice(&s[..len]);
+            self.len += len;
+
+            s = &s[len..];
+        }
+
+        Ok(())
+    }
+}
+
+/// The interned tag of `target`, or [`None`] if the [`TAGS`] table is full
+fn tag(target: &str) -> Option<&'static CStr> {
+    // A tag ends at the first NUL byte, if any
+    let target = target.as_bytes();
+    let target = &target[..target
+        .iter()
+        .position(|b| *b == 0)
+        .unwrap_or(target.len())
+        .min(LOG_TAG_MAX_LEN)];
+
+    TAGS.intern(target)
+}
+
+const TAG_FREE: u8 = 0;
+const TAG_CLAIMED: u8 = 1;
+const TAG_READY: u8 = 2;
+
+/// A table of NUL-terminated tags, with a `'static` address each.
+///
+/// Entries are claimed lock-free and never freed. Two threads racing to intern
+/// the same target might both add an entry for it, which is harmless.
+struct Tags([Tag; 64]);
+
+struct Tag {
+    state: AtomicU8,
+    name: UnsafeCell<[u8; LOG_TAG_MAX_LEN + 1]>,
+}
+
+unsafe impl Sync for Tags {}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const TAG: Tag = Tag {
+    state: AtomicU8::new(TAG_FREE),
+    name: UnsafeCell::new([0; LOG_TAG_MAX_LEN + 1]),
+};
+
+static TAGS: Tags = Tags([TAG; 64]);
+
+impl Tags {
+    fn intern(&'static self, target: &[u8]) -> Option<&'static CStr> {
+        for tag in &self.0 {
+            match tag.state.load(Ordering::Acquire) {
+                TAG_READY if tag.name().to_bytes() == target => return Some(tag.name()),
+                TAG_FREE if tag.claim() => return Some(tag.set(target)),
+                _ => (),
+            }
+        }
+
+        None
+    }
+}
+
+impl Tag {
+    fn claim(&self) -> bool {
+        self.state
+            .compare_exchange(TAG_FREE, TAG_CLAIMED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    fn set(&self, target: &[u8]) -> &CStr {
+        // The entry is claimed, i.e. nobody else writes or reads it
+        unsafe {
+            let name = &mut *self.name.get();
+            name[..target.len()].copy_from_slice(target);
+            name[target.len()] = 0;
+        }
+
+        self.state.store(TAG_READY, Ordering::Release);
+
+        self.name()
+    }
+
+    fn name(&self) -> &CStr {
+        // Only called once the entry is ready, i.e. never written to again
+        unsafe { CStr::from_ptr((*self.name.get()).as_ptr() as *const c_char) }
+    }
+}
+
+/// The maximum length of an ESP-IDF log message captured by [`capture_esp_log`];
+/// longer messages are truncated
+pub const CAPTURE_LOG_MESSAGE_MAX_LEN: usize = 255;
+
+/// Passes the output of the ESP-IDF logging (`ESP_LOGI` etc. of the C components)
+/// as records to the logger of the [`log`](::log) crate, by hooking
+/// `esp_log_set_vprintf`.
+///
+/// The level, tag and message of each log line are parsed from its text, so the
+/// ESP-IDF log format must not be customized. Output which does not look like a
+/// log line (e.g. the continuation of a message printed in several calls) and the
+/// messages written by [`EspLogger`] are passed on to the previously installed
+/// `vprintf`-like function instead - by default `vprintf`, writing to `stdout`.
+/// The latter allows for combining the capture with [`EspLogger`], which then
+/// prints the C and the Rust logs alike.
+///
+/// Returns a guard which re-installs the previous `vprintf`-like function when
+/// dropped. Only one capture can be active at a time; the function fails with
+/// `EBUSY` otherwise.
+pub fn capture_esp_log() -> Result<EspLogCapture, EspErrno> {
+    if CAPTURING
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+        .is_err()
+    {
+        return Err(EspErrno::from(EBUSY as _).unwrap());
+    }
+
+    let previous = unsafe { esp_log_set_vprintf(Some(capture)) };
+
+    CAPTURE_PREVIOUS.store(
+        previous.map_or(ptr::null_mut(), |previous| previous as *mut _),
+        Ordering::Release,
+    );
+
+    Ok(EspLogCapture(()))
+}
+
+/// A guard returned by [`capture_esp_log`], which ends the capture when dropped
+#[derive(Debug)]
+#[must_use = "dropping the guard immediately ends the capture"]
+pub struct EspLogCapture(());
+
+impl EspLogCapture {
+    /// Keeps the capture in place for the rest of the program.
+    pub fn leak(self) {
+        core::mem::forget(self);
+    }
+}
+
+impl Drop for EspLogCapture {
+    fn drop(&mut self) {
+        let previous = CAPTURE_PREVIOUS.load(Ordering::Acquire);
+
+        unsafe {
+            esp_log_set_vprintf(if previous.is_null() {
+                None
+            } else {
+                Some(core::mem::transmute::<*mut c_void, VprintfLike>(previous))
+            });
+        }
+
+        CAPTURING.store(false, Ordering::Release);
+    }
+}
+
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+static CAPTURE_PREVIOUS: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+unsafe extern "C" fn capture(format: *const c_char, args: VaList) -> c_int {
+    if format != RUST_FORMAT.as_ptr() {
+        let mut buf = [0u8; CAPTURE_LOG_MESSAGE_MAX_LEN + 1];
+
+        // Format a copy, as the arguments might be passed on to the previous function below
+        let mut copy = VaListCopy::new(args);
+        let len = vsnprintf(
+            buf.as_mut_ptr() as *mut _,
+            buf.len(),
+            format,
+            copy.as_va_list(),
+        );
+
+        if len >= 0 {
+            let line = &buf[..(len as usize).min(CAPTURE_LOG_MESSAGE_MAX_LEN)];
+            let line = match core::str::from_utf8(line) {
+                Ok(line) => line,
+                // Truncated in the middle of a character, or not UTF-8 at all
+                Err(err) => core::str::from_utf8_unchecked(&line[..err.valid_up_to()]),
+            };
+
+            if let Some((level, tag, message)) = parse(line) {
+                if level <= ::log::max_level() {
+                    ::log::logger().log(
+                        &Record::builder()
+                            .args(format_args!("{message}"))
+                            .level(level)
+                            .target(tag)
+                            .build(),
+                    );
+                }
+
+                return len;
+            }
+        }
+    }
+
+    let previous = CAPTURE_PREVIOUS.load(Ordering::Acquire);
+    if previous.is_null() {
+        return 0;
+    }
+
+    core::mem::transmute::<*mut c_void, VprintfLike>(previous)(format, args)
+}
+
+/// Splits a line in the ESP-IDF log format - `[<color>]<L> (<timestamp>) <tag>: <message>[<reset>]` -
+/// into its level, tag and message
+fn parse(line: &str) -> Option<(Level, &str, &str)> {
+    let mut line = line.trim_end_matches(['\r', '\n']);
+
+    if let Some(colored) = line.strip_prefix("\x1b[") {
+        line = &colored[colored.find('m')? + 1..];
+        line = line.strip_suffix("\x1b[0m").unwrap_or(line);
+    }
+
+    let level = match line.as_bytes().first()? {
+        b'E' => Level::Error,
+        b'W' => Level::Warn,
+        b'I' => Level::Info,
+        b'D' => Level::Debug,
+        b'V' => Level::Trace,
+        _ => return None,
+    };
+
+    let (_timestamp, line) = line[1..].strip_prefix(" (")?.split_once(") ")?;
+
+    let (tag, message) = line.split_once(": ")?;
+
+    Some((level, tag, message))
+}
@@ -1,7 +1,247 @@
+//! The `no_std` panic handler of the `panic_handler` feature.
+//!
+//! It prints the panic message and location with `esp_rom_printf` - which works even
+//! with a broken heap or in an interrupt - followed by a backtrace, stores the message
+//! in memory which survives a reset, and then takes the [`PanicAction`].
+
 #![cfg(all(not(feature = "std"), feature = "panic_handler"))]
 
+use core::fmt::{self, Write};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::*;
+
+/// The maximum length (in bytes) of the panic message kept across a reset;
+/// longer messages are truncated
+pub const PANIC_MESSAGE_MAX_LEN: usize = 256;
+
+/// The number of stack frames printed by the panic handler
+const BACKTRACE_DEPTH: core::ffi::c_int = 32;
+
+/// Marks a valid [`Record`]; anything else is the garbage left by a power-on reset
+const RECORD_MAGIC: u32 = 0x5275_5374;
+
+/// What the panic handler does after printing and storing the panic message
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PanicAction {
+    /// Call `abort`, i.e. take the action of the ESP-IDF panic handler, as configured
+    /// with `CONFIG_ESP_SYSTEM_PANIC` (the default)
+    Abort,
+    /// Restart with `esp_restart`
+    Restart,
+    /// Enter deep sleep with `esp_deep_sleep_start`, with the wakeup sources
+    /// configured so far
+    DeepSleep,
+}
+
+/// Sets what the panic handler does after printing and storing the panic message.
+pub fn set_panic_action(action: PanicAction) {
+    let action = match action {
+        PanicAction::Abort => ACTION_ABORT,
+        PanicAction::Restart => ACTION_RESTART,
+        PanicAction::DeepSleep => ACTION_DEEP_SLEEP,
+    };
+
+    ACTION.store(action, Ordering::Release);
+}
+
+/// Gets what the panic handler does after printing and storing the panic message;
+/// see [`set_panic_action`].
+pub fn panic_action() -> PanicAction {
+    match ACTION.load(Ordering::Acquire) {
+        ACTION_RESTART => PanicAction::Restart,
+        ACTION_DEEP_SLEEP => PanicAction::DeepSleep,
+        _ => PanicAction::Abort,
+    }
+}
+
+const ACTION_ABORT: u8 = 0;
+const ACTION_RESTART: u8 = 1;
+const ACTION_DEEP_SLEEP: u8 = 2;
+
+static ACTION: AtomicU8 = AtomicU8::new(ACTION_ABORT);
+
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// The panic message of the panic which caused the last reset, together with the
+/// reason of that reset; see [`last_panic`].
+#[derive(Clone)]
+pub struct LastPanic {
+    message: [u8; PANIC_MESSAGE_MAX_LEN],
+    len: usize,
+    reset_reason: esp_reset_reason_t,
+}
+
+impl LastPanic {
+    /// The panic message and location, possibly truncated to
+    /// [`PANIC_MESSAGE_MAX_LEN`] bytes
+    pub fn message(&self) -> &str {
+        // The handler only truncates at character boundaries
+        core::str::from_utf8(&self.message[..self.len]).unwrap_or_default()
+    }
+
+    /// The reason of the reset following the panic, e.g. `ESP_RST_PANIC` after
+    /// [`PanicAction::Abort`] or `ESP_RST_SW` after [`PanicAction::Restart`]
+    pub fn reset_reason(&self) -> esp_reset_reason_t {
+        self.reset_reason
+    }
+}
+
+impl fmt::Debug for LastPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LastPanic")
+            .field("message", &self.message())
+            .field("reset_reason", &self.reset_reason)
+            .finish()
+    }
+}
+
+impl fmt::Display for LastPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// Returns the panic message stored by the panic handler before the last reset, if
+/// any; it is kept until [`clear_last_panic`] is called, or power is lost.
+pub fn last_panic() -> Option<LastPanic> {
+    let record = unsafe { ptr::read_volatile(ptr::addr_of!(RECORD)) };
+
+    if record.magic != RECORD_MAGIC
+        || record.len as usize > PANIC_MESSAGE_MAX_LEN
+        || record.checksum != checksum(&record.message[..record.len as usize])
+    {
+        return None;
+    }
+
+    Some(LastPanic {
+        message: record.message,
+        len: record.len as _,
+        reset_reason: unsafe { esp_reset_reason() },
+    })
+}
+
+/// Forgets the panic message stored by the panic handler, so that [`last_panic`]
+/// returns `None` until the next panic.
+pub fn clear_last_panic() {
+    unsafe {
+        ptr::write_volatile(ptr::addr_of_mut!(RECORD.magic), 0);
+    }
+}
+
+/// The panic message, as stored in memory which is not initialized on reset
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Record {
+    magic: u32,
+    len: u32,
+    checksum: u32,
+    message: [u8; PANIC_MESSAGE_MAX_LEN],
+}
+
+// The ESP32-C2 has no RTC memory, so the message only survives software resets.
+// The sections are not loaded, so the initializer is never written: it only makes
+// reading whatever the memory holds - a record, or garbage - well-defined
+#[cfg_attr(not(esp32c2), link_section = ".rtc_noinit")]
+#[cfg_attr(esp32c2, link_section = ".noinit")]
+static mut RECORD: Record = Record {
+    magic: 0,
+    len: 0,
+    checksum: 0,
+    message: [0; PANIC_MESSAGE_MAX_LEN],
+};
+
+fn checksum(message: &[u8]) -> u32 {
+    // FNV-1a
+    message.iter().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Prints everything written to it with `esp_rom_printf`, and keeps as much of it as
+/// fits in the `Record`
+struct Output {
+    message: [u8; PANIC_MESSAGE_MAX_LEN],
+    len: usize,
+}
+
+impl Output {
+    /// The size of the NUL-terminated chunks passed to `esp_rom_printf`
+    const CHUNK_LEN: usize = 64;
+
+    fn print(s: &str) {
+        for chunk in s.as_bytes().chunks(Self::CHUNK_LEN) {
+            let mut buf = [0; Self::CHUNK_LEN + 1];
+            buf[..chunk.len()].copy_from_slice(chunk);
+
+            unsafe {
+                esp_rom_printf(c"%s".as_ptr(), buf.as_ptr());
+            }
+        }
+    }
+
+    fn store(&mut self) {
+        let record = Record {
+            magic: RECORD_MAGIC,
+            len: self.len as _,
+            checksum: checksum(&self.message[..self.len]),
+            message: self.message,
+        };
+
+        unsafe {
+            ptr::write_volatile(ptr::addr_of_mut!(RECORD), record);
+        }
+    }
+}
+
+impl Write for Output {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        Self::print(s);
+
+        let mut len = s.len().min(PANIC_MESSAGE_MAX_LEN - self.len);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        self.message[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+
+        Ok(())
+    }
+}
+
 #[cfg_attr(not(feature = "std"), panic_handler)]
 #[allow(dead_code)]
-fn panic(_info: &core::panic::PanicInfo) -> ! {
-    unsafe { crate::abort() }
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    // Don't try to format the message of a panic while formatting the message of a panic
+    if !PANICKING.swap(true, Ordering::AcqRel) {
+        let mut output = Output {
+            message: [0; PANIC_MESSAGE_MAX_LEN],
+            len: 0,
+        };
+
+        Output::print("\n");
+
+        let _ = match info.location() {
+            Some(location) => write!(output, "panicked at {location}:\n{}", info.message()),
+            None => write!(output, "panicked:\n{}", info.message()),
+        };
+
+        output.store();
+
+        Output::print("\n");
+
+        unsafe {
+            esp_backtrace_print(BACKTRACE_DEPTH);
+        }
+
+        match panic_action() {
+            PanicAction::Abort => (),
+            PanicAction::Restart => unsafe { esp_restart() },
+            PanicAction::DeepSleep => unsafe { esp_deep_sleep_start() },
+        }
+    }
+
+    unsafe { abort() }
 }
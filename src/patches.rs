@@ -1,21 +1,61 @@
-// (Temporary code) ESP-IDF does not (yet) have a pthread rwlock implementation, which is required by STD
-// We provide a quick and very hacky implementation here
 mod atexit;
 #[cfg(feature = "std")]
 mod lstat;
+#[cfg(feature = "std")]
+pub use lstat::{register_lstat, unregister_lstat, LstatFn};
+#[cfg(feature = "std")]
+mod symlink;
+// POSIX functions some ESP-IDF versions are missing: the build script looks for their
+// definitions in the ESP-IDF sources, and outputs the `esp_idf_sys_patch_<function>` cfg
+// for each function it does not find (see `build/patches.rs`)
+#[cfg(all(feature = "std", esp_idf_sys_patch_clock_nanosleep))]
+mod clock_nanosleep;
+#[cfg(all(feature = "std", esp_idf_sys_patch_pthread_setname_np))]
+mod pthread_setname;
+#[cfg(all(
+    feature = "std",
+    any(esp_idf_sys_patch_getrandom, esp_idf_sys_patch_getentropy)
+))]
+mod random;
+
+// With the native builder, the patches are linked because the build script marks their
+// symbols as undefined (see `build/patches.rs`). The `pio` builder and ESP-IDF CMake
+// projects do not take these linker arguments, so `link_patches` references the patches
+// as well
 
 #[allow(dead_code)]
-pub struct PatchesRef(*mut core::ffi::c_void, *mut core::ffi::c_void);
+pub struct PatchesRef(());
 
-/// A hack to make sure that certain symbols are linked to the final executable
-/// Call this function once e.g. in the beginning of your main function
 pub fn link_patches() -> PatchesRef {
+    reference(atexit::atexit as _);
+    reference(atexit::__cxa_atexit as _);
+    reference(atexit::at_quick_exit as _);
+    reference(atexit::exit as _);
+    reference(atexit::quick_exit as _);
+
     #[cfg(feature = "std")]
-    let lstat = lstat::link_patches();
-    #[cfg(not(feature = "std"))]
-    let lstat = core::ptr::null_mut();
+    {
+        reference(lstat::lstat as _);
+        reference(symlink::readlink as _);
+        reference(symlink::symlink as _);
+        #[cfg(esp_idf_sys_patch_realpath)]
+        reference(symlink::realpath as _);
+    }
+
+    #[cfg(all(feature = "std", esp_idf_sys_patch_clock_nanosleep))]
+    reference(clock_nanosleep::clock_nanosleep as _);
+    #[cfg(all(feature = "std", esp_idf_sys_patch_pthread_setname_np))]
+    reference(pthread_setname::pthread_setname_np as _);
+    #[cfg(all(feature = "std", esp_idf_sys_patch_getrandom))]
+    reference(random::getrandom as _);
+    #[cfg(all(feature = "std", esp_idf_sys_patch_getentropy))]
+    reference(random::getentropy as _);
 
-    let atexit = atexit::link_patches();
+    PatchesRef(())
+}
 
-    PatchesRef(lstat, atexit)
+/// Keep a reference to `patch` in the code, so that the linker pulls it in
+#[inline(never)]
+fn reference(patch: *const core::ffi::c_void) {
+    core::hint::black_box(patch);
 }
@@ -1,13 +1,236 @@
+// The `atexit` family of functions, on top of a registry of our own.
+//
+// ESP-IDF has no notion of a process exiting, and the `atexit` of the C library is only
+// run by `exit`, which on ESP-IDF aborts. Forwarding `atexit` to `esp_register_shutdown_handler`
+// runs the handlers on `esp_restart`, but its table is small and fixed, and `__cxa_atexit` -
+// used for the destructors of C++ statics - and `at_quick_exit` have no equivalent.
+//
+// Instead, the handlers are kept in heap-allocated lists guarded by a critical section,
+// and are called in the reverse order of their registration, as the C standard mandates:
+// - `atexit` and `__cxa_atexit` handlers are called by `exit` and - through a single
+//   shutdown handler - by `esp_restart`, including the restart after a panic with
+//   `PanicAction::Restart`
+// - `at_quick_exit` handlers are called by `quick_exit`
+//
+// Registration failures return `-1` and set `errno` to `ENOMEM`, or to `EINVAL` for a
+// null function.
+//
+// `__cxa_thread_atexit` - registering the destructors of C++ `thread_local` objects - is
+// not patched: the C library of the ESP-IDF toolchains has no `__cxa_thread_atexit_impl`,
+// so the one of `libsupc++` keeps these destructors in pthread keys of its own, which
+// ESP-IDF runs when the thread exits.
+
+use crate::errno::set_errno;
+use crate::*;
+use core::cell::UnsafeCell;
 use core::ffi;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// `taskSCHEDULER_NOT_STARTED`, which is defined with a cast, and is thus not part of
+/// the bindings
+const SCHEDULER_NOT_STARTED: BaseType_t = 1;
+
+static EXIT_HANDLERS: Handlers = Handlers::new();
+static QUICK_EXIT_HANDLERS: Handlers = Handlers::new();
+
+static SHUTDOWN_HANDLER_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Copy, Clone)]
+enum Function {
+    Plain(unsafe extern "C" fn()),
+    WithArg(unsafe extern "C" fn(*mut ffi::c_void), *mut ffi::c_void),
+}
+
+struct Handler {
+    function: Function,
+    next: *mut Handler,
+}
+
+/// A list of handlers, latest first
+struct Handlers {
+    locked: AtomicBool,
+    head: UnsafeCell<*mut Handler>,
+}
+
+// The list is only accessed in its critical section
+unsafe impl Sync for Handlers {}
+
+impl Handlers {
+    const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            head: UnsafeCell::new(ptr::null_mut()),
+        }
+    }
+
+    fn push(&self, function: Function) -> ffi::c_int {
+        let handler =
+            unsafe { heap_caps_malloc(core::mem::size_of::<Handler>() as _, MALLOC_CAP_8BIT as _) }
+                as *mut Handler;
+
+        if handler.is_null() {
+            set_errno(ENOMEM as _);
+            return -1;
+        }
+
+        self.critical_section(|head| unsafe {
+            handler.write(Handler {
+                function,
+                next: *head,
+            });
+
+            *head = handler;
+        });
+
+        0
+    }
+
+    fn pop(&self) -> Option<Function> {
+        let handler = self.critical_section(|head| {
+            let handler = *head;
+
+            if !handler.is_null() {
+                *head = unsafe { (*handler).next };
+            }
+
+            handler
+        });
+
+        if handler.is_null() {
+            return None;
+        }
+
+        let function = unsafe { (*handler).function };
+
+        unsafe {
+            heap_caps_free(handler as *mut _);
+        }
+
+        Some(function)
+    }
+
+    /// Run `f` on the head of the list in a critical section: the scheduler of the current
+    /// core is suspended, so that no task waiting for the list can preempt `f`, and the
+    /// other core is kept out by spinning. Before the scheduler starts (e.g. while the
+    /// constructors of C++ statics register their destructors), there is nothing to
+    /// guard against.
+    fn critical_section<R>(&self, f: impl FnOnce(&mut *mut Handler) -> R) -> R {
+        let scheduler = unsafe { xTaskGetSchedulerState() } != SCHEDULER_NOT_STARTED;
+
+        if scheduler {
+            unsafe {
+                vTaskSuspendAll();
+            }
+        }
+
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        let result = f(unsafe { &mut *self.head.get() });
 
-static mut __ATEXIT_INTERNAL_REFERENCE: *mut ffi::c_void = atexit as *mut _;
+        self.locked.store(false, Ordering::Release);
 
-pub fn link_patches() -> *mut ffi::c_void {
-    unsafe { __ATEXIT_INTERNAL_REFERENCE }
+        if scheduler {
+            unsafe {
+                xTaskResumeAll();
+            }
+        }
+
+        result
+    }
+
+    /// Call the handlers - including the ones registered by the handlers themselves -
+    /// latest first, removing them
+    fn run(&self) {
+        while let Some(function) = self.pop() {
+            match function {
+                Function::Plain(f) => unsafe { f() },
+                Function::WithArg(f, arg) => unsafe { f(arg) },
+            }
+        }
+    }
+}
+
+fn register_exit(function: Function) -> ffi::c_int {
+    if !SHUTDOWN_HANDLER_REGISTERED.swap(true, Ordering::AcqRel) {
+        // Should ESP-IDF run out of shutdown handler slots, the handlers still run on `exit`
+        unsafe {
+            esp_register_shutdown_handler(Some(run_exit_handlers));
+        }
+    }
+
+    EXIT_HANDLERS.push(function)
+}
+
+extern "C" fn run_exit_handlers() {
+    EXIT_HANDLERS.run();
 }
 
 #[no_mangle]
 #[inline(never)]
 pub unsafe extern "C" fn atexit(f: Option<unsafe extern "C" fn()>) -> ffi::c_int {
-    crate::esp_register_shutdown_handler(f)
+    match f {
+        Some(f) => register_exit(Function::Plain(f)),
+        None => {
+            set_errno(EINVAL as _);
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn __cxa_atexit(
+    f: Option<unsafe extern "C" fn(*mut ffi::c_void)>,
+    arg: *mut ffi::c_void,
+    _dso_handle: *mut ffi::c_void,
+) -> ffi::c_int {
+    match f {
+        Some(f) => register_exit(Function::WithArg(f, arg)),
+        None => {
+            set_errno(EINVAL as _);
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn at_quick_exit(f: Option<unsafe extern "C" fn()>) -> ffi::c_int {
+    match f {
+        Some(f) => QUICK_EXIT_HANDLERS.push(Function::Plain(f)),
+        None => {
+            set_errno(EINVAL as _);
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn exit(status: ffi::c_int) -> ! {
+    EXIT_HANDLERS.run();
+
+    fflush(ptr::null_mut());
+
+    _exit(status)
+}
+
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn quick_exit(status: ffi::c_int) -> ! {
+    QUICK_EXIT_HANDLERS.run();
+
+    _exit(status)
+}
+
+extern "C" {
+    // Declare it manually, as `unistd.h` is not necessarily part of the bindings
+    fn _exit(status: ffi::c_int) -> !;
 }
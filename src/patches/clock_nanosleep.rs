@@ -0,0 +1,79 @@
+// `clock_nanosleep`, for the ESP-IDF versions which only provide `nanosleep`. The sleep is
+// done with `vTaskDelay`, rounded up to whole ticks, so - just like with `nanosleep` - it is
+// never shorter than requested, but may be up to two ticks longer.
+
+use crate::*;
+use core::ffi;
+
+/// As in the `time.h` of the C library, which only defines it with `_POSIX_TIMERS`
+const TIMER_ABSTIME: ffi::c_int = 4;
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn clock_nanosleep(
+    clock_id: clockid_t,
+    flags: ffi::c_int,
+    request: *const timespec,
+    remain: *mut timespec,
+) -> ffi::c_int {
+    if request.is_null() {
+        return EFAULT as _;
+    }
+
+    let request = &*request;
+
+    // `time_t` is 32 or 64 bits wide, depending on the ESP-IDF version
+    let (mut secs, mut nsecs): (i64, i64) = (request.tv_sec as _, request.tv_nsec as _);
+
+    if !(0..NANOS_PER_SEC).contains(&nsecs) {
+        return EINVAL as _;
+    }
+
+    let mut now: timespec = core::mem::zeroed();
+
+    // Also rejects the clocks ESP-IDF does not know about
+    if clock_gettime(clock_id, &mut now) != 0 {
+        return EINVAL as _;
+    }
+
+    if flags & TIMER_ABSTIME != 0 {
+        let (now_secs, now_nsecs): (i64, i64) = (now.tv_sec as _, now.tv_nsec as _);
+
+        secs = secs.saturating_sub(now_secs);
+        nsecs -= now_nsecs;
+
+        if nsecs < 0 {
+            secs = secs.saturating_sub(1);
+            nsecs += NANOS_PER_SEC;
+        }
+    }
+
+    if secs >= 0 && (secs, nsecs) != (0, 0) {
+        // Round up, plus one tick for the part of the current tick which has already passed.
+        // The seconds and nanoseconds are converted separately, so that nothing overflows:
+        // `nsecs` is below one second, and sleeps too long for a `u64` of ticks saturate
+        let mut ticks = (secs as u64)
+            .saturating_mul(CONFIG_FREERTOS_HZ as u64)
+            .saturating_add(
+                (nsecs as u64 * CONFIG_FREERTOS_HZ as u64).div_ceil(NANOS_PER_SEC as u64),
+            )
+            .saturating_add(1);
+
+        while ticks > 0 {
+            let delay = ticks.min(TickType_t::MAX as u64);
+
+            vTaskDelay(delay as _);
+
+            ticks -= delay;
+        }
+    }
+
+    // Nothing interrupts the sleep
+    if flags & TIMER_ABSTIME == 0 && !remain.is_null() {
+        *remain = core::mem::zeroed();
+    }
+
+    0
+}
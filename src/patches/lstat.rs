@@ -1,10 +1,126 @@
-use crate::stat;
-use core::ffi;
+// ESP-IDF has no `lstat`: `esp_vfs_t` has no operation for it, and none of the VFS drivers of
+// ESP-IDF have a notion of symbolic links. VFS drivers which do can register an `lstat` of
+// their own for their base path with `register_lstat`; for all other paths, `lstat` is
+// equivalent to `stat`. See also `symlink.rs`
 
-static mut __LSTAT_INTERNAL_REFERENCE: *mut ffi::c_void = lstat as *mut _;
+use crate::errno::set_errno;
+use crate::*;
+use core::ffi::{self, CStr};
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
 
-pub fn link_patches() -> *mut ffi::c_void {
-    unsafe { __LSTAT_INTERNAL_REFERENCE }
+/// The `lstat` operation of a VFS driver, called with the path relative to the base path
+/// of the driver - like the operations of `esp_vfs_t` - and returning `-1` with `errno`
+/// set on failure
+pub type LstatFn = unsafe extern "C" fn(path: *const ffi::c_char, buf: *mut stat) -> ffi::c_int;
+
+/// The maximum number of VFS drivers with an `lstat` of their own
+const LSTAT_DRIVERS_MAX: usize = 8;
+
+/// A VFS driver with an `lstat` of its own; an entry with a base path is in use, an entry
+/// with an `lstat` function only is being registered
+struct LstatDriver {
+    base_path: AtomicPtr<ffi::c_char>,
+    lstat: AtomicPtr<ffi::c_void>,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_DRIVER: LstatDriver = LstatDriver {
+    base_path: AtomicPtr::new(ptr::null_mut()),
+    lstat: AtomicPtr::new(ptr::null_mut()),
+};
+
+static LSTAT_DRIVERS: [LstatDriver; LSTAT_DRIVERS_MAX] = [NO_DRIVER; LSTAT_DRIVERS_MAX];
+
+/// Registers `lstat` as the `lstat` operation of the VFS driver registered at
+/// `base_path`, i.e. of a driver which supports symbolic links.
+///
+/// Fails with `ESP_ERR_INVALID_STATE` if an `lstat` is already registered for
+/// `base_path`, and with `ESP_ERR_NO_MEM` if there is no room for another one.
+pub fn register_lstat(base_path: &'static CStr, lstat: LstatFn) -> Result<(), EspError> {
+    if LSTAT_DRIVERS
+        .iter()
+        .any(|driver| driver.base_path() == Some(base_path))
+    {
+        return Err(EspError::from_infallible::<ESP_ERR_INVALID_STATE>());
+    }
+
+    for driver in &LSTAT_DRIVERS {
+        // Claim the entry with the function, and only then publish it with the base path
+        if driver
+            .lstat
+            .compare_exchange(
+                ptr::null_mut(),
+                lstat as *mut _,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+        {
+            driver
+                .base_path
+                .store(base_path.as_ptr() as *mut _, Ordering::Release);
+
+            return Ok(());
+        }
+    }
+
+    Err(EspError::from_infallible::<ESP_ERR_NO_MEM>())
+}
+
+/// Unregisters the `lstat` operation registered for `base_path` with [`register_lstat`].
+///
+/// Fails with `ESP_ERR_INVALID_STATE` if no `lstat` is registered for `base_path`.
+pub fn unregister_lstat(base_path: &CStr) -> Result<(), EspError> {
+    let driver = LSTAT_DRIVERS
+        .iter()
+        .find(|driver| driver.base_path() == Some(base_path))
+        .ok_or(EspError::from_infallible::<ESP_ERR_INVALID_STATE>())?;
+
+    // Unpublish the entry first, and only then free it
+    driver.base_path.store(ptr::null_mut(), Ordering::Release);
+    driver.lstat.store(ptr::null_mut(), Ordering::Release);
+
+    Ok(())
+}
+
+impl LstatDriver {
+    fn base_path(&self) -> Option<&'static CStr> {
+        let base_path = self.base_path.load(Ordering::Acquire);
+
+        (!base_path.is_null()).then(|| unsafe { CStr::from_ptr(base_path) })
+    }
+
+    fn lstat(&self) -> Option<LstatFn> {
+        let lstat = self.lstat.load(Ordering::Acquire);
+
+        (!lstat.is_null())
+            .then(|| unsafe { core::mem::transmute::<*mut ffi::c_void, LstatFn>(lstat) })
+    }
+}
+
+/// Returns the `lstat` of the driver `path` belongs to - as per the rules of the VFS, the
+/// one with the longest base path which is `path` itself or a parent directory of it -
+/// together with the path relative to the base path of the driver
+fn driver(path: &CStr) -> Option<(LstatFn, &CStr)> {
+    let bytes = path.to_bytes();
+
+    LSTAT_DRIVERS
+        .iter()
+        .filter_map(|driver| {
+            let base_path = driver.base_path()?.to_bytes();
+
+            let matches = bytes.starts_with(base_path)
+                && matches!(bytes.get(base_path.len()), None | Some(b'/'));
+
+            matches.then_some((driver, base_path.len()))
+        })
+        .max_by_key(|(_, len)| *len)
+        .and_then(|(driver, len)| {
+            let relative = unsafe { CStr::from_ptr(path.as_ptr().add(len)) };
+
+            Some((driver.lstat()?, relative))
+        })
 }
 
 #[no_mangle]
@@ -18,5 +134,13 @@ pub unsafe extern "C" fn lstat(path: *const ffi::c_char, buf: *mut stat) -> ffi:
         fn esp_idf_sys_stat(path: *const ffi::c_char, buf: *mut stat) -> ffi::c_int;
     }
 
-    esp_idf_sys_stat(path, buf)
+    if path.is_null() {
+        set_errno(EFAULT as _);
+        return -1;
+    }
+
+    match driver(CStr::from_ptr(path)) {
+        Some((lstat, relative)) => lstat(relative.as_ptr(), buf),
+        None => esp_idf_sys_stat(path, buf),
+    }
 }
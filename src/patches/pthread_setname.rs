@@ -0,0 +1,57 @@
+// `pthread_setname_np`, for the ESP-IDF versions which do not provide it, renames the
+// FreeRTOS task of the calling thread. FreeRTOS has no API for that, but the name returned
+// by `pcTaskGetName` is an array in the task control block, whose layout `StaticTask_t`
+// mirrors: the name is written there, provided that it is where `pcTaskGetName` says.
+//
+// Only the calling thread can be renamed, as ESP-IDF has no public API mapping a
+// `pthread_t` to its task. Just like `pthread_self`, which it compares `thread` with, the
+// function must be called from a pthread - `pthread_self` asserts otherwise.
+
+use crate::*;
+use core::{ffi, ptr};
+
+/// The length of the name array of a task, NUL included
+const TASK_NAME_LEN: usize = task_name_len(|task| &task.ucDummy7);
+
+const _: () = assert!(TASK_NAME_LEN == CONFIG_FREERTOS_MAX_TASK_NAME_LEN as usize);
+
+const fn task_name_len<T, const N: usize>(_name: fn(&StaticTask_t) -> &[T; N]) -> usize {
+    N
+}
+
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn pthread_setname_np(
+    thread: pthread_t,
+    name: *const ffi::c_char,
+) -> ffi::c_int {
+    if name.is_null() {
+        return EINVAL as _;
+    }
+
+    if pthread_equal(thread, pthread_self()) == 0 {
+        return ESRCH as _;
+    }
+
+    let name = ffi::CStr::from_ptr(name).to_bytes_with_nul();
+
+    if name.len() > TASK_NAME_LEN {
+        return ERANGE as _;
+    }
+
+    let task = xTaskGetCurrentTaskHandle();
+    let task_name = ptr::addr_of_mut!((*(task as *mut StaticTask_t)).ucDummy7) as *mut u8;
+
+    #[cfg(esp_idf_version_at_least_4_4_0)]
+    let current_name = pcTaskGetName(task);
+    #[cfg(not(esp_idf_version_at_least_4_4_0))]
+    let current_name = pcTaskGetTaskName(task);
+
+    if current_name as *mut u8 != task_name {
+        return ENOSYS as _;
+    }
+
+    ptr::copy_nonoverlapping(name.as_ptr(), task_name, name.len());
+
+    0
+}
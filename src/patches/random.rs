@@ -0,0 +1,38 @@
+// `getrandom` and `getentropy` on top of the hardware RNG, for the ESP-IDF versions which
+// do not provide them (see `build/patches.rs`)
+
+use crate::errno::set_errno;
+use crate::*;
+use core::ffi;
+
+/// The largest buffer `getentropy` fills, as per POSIX
+#[cfg(esp_idf_sys_patch_getentropy)]
+const GETENTROPY_MAX: usize = 256;
+
+#[cfg(esp_idf_sys_patch_getrandom)]
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn getrandom(
+    buf: *mut ffi::c_void,
+    buflen: usize,
+    _flags: ffi::c_uint,
+) -> isize {
+    // The hardware RNG never blocks, so `GRND_NONBLOCK` and `GRND_RANDOM` make no difference
+    esp_fill_random(buf, buflen as _);
+
+    buflen as _
+}
+
+#[cfg(esp_idf_sys_patch_getentropy)]
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn getentropy(buf: *mut ffi::c_void, buflen: usize) -> ffi::c_int {
+    if buflen > GETENTROPY_MAX {
+        set_errno(EIO as _);
+        return -1;
+    }
+
+    esp_fill_random(buf, buflen as _);
+
+    0
+}
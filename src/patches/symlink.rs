@@ -0,0 +1,61 @@
+// None of the ESP-IDF VFS drivers (FAT, SPIFFS, LittleFS, ...) support symbolic links -
+// `esp_vfs_t` does not even have operations for them - so there are no links to follow,
+// read or create:
+// - `lstat` is `stat`, unless the VFS driver of the path registered an `lstat` of its own
+//   (see `lstat.rs`)
+// - `readlink` fails with `EINVAL` for any existing file, as it is not a link, and with
+//   the error of `lstat` (e.g. `ENOENT`) otherwise
+// - `symlink` fails with `ENOSYS`
+// - `realpath` - which newer ESP-IDF versions provide, as a purely lexical implementation -
+//   fails with `ENOSYS` with the versions which lack it (see `build/patches.rs`)
+
+use crate::errno::set_errno;
+use crate::*;
+use core::ffi;
+
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn readlink(
+    path: *const ffi::c_char,
+    _buf: *mut ffi::c_char,
+    _bufsize: usize,
+) -> isize {
+    let mut buf = core::mem::MaybeUninit::<stat>::uninit();
+
+    if super::lstat::lstat(path, buf.as_mut_ptr()) != 0 {
+        // `errno` is set by `lstat`
+        return -1;
+    }
+
+    if buf.assume_init().st_mode & S_IFMT == S_IFLNK {
+        // Should a VFS driver ever report a link, there is still no way to read it
+        set_errno(ENOSYS as _);
+    } else {
+        set_errno(EINVAL as _);
+    }
+
+    -1
+}
+
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn symlink(
+    _target: *const ffi::c_char,
+    _link_path: *const ffi::c_char,
+) -> ffi::c_int {
+    set_errno(ENOSYS as _);
+
+    -1
+}
+
+#[cfg(esp_idf_sys_patch_realpath)]
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn realpath(
+    _path: *const ffi::c_char,
+    _resolved_path: *mut ffi::c_char,
+) -> *mut ffi::c_char {
+    set_errno(ENOSYS as _);
+
+    core::ptr::null_mut()
+}
@@ -0,0 +1,54 @@
+//! The registry of the functions called before the Rust `main` function, by the
+//! `binstart`/`libstart` glue.
+//!
+//! Every `#[pre_main]` function gets a [`PreMain`] entry in a
+//! `.esp_idf_sys_pre_main.<priority>` section. The linker fragment of the `main`
+//! component of the ESP-IDF build places all these sections - of all crates -
+//! next to each other, sorted by their priority, between the
+//! `_esp_idf_sys_pre_main_start` and `_esp_idf_sys_pre_main_end` symbols.
+//!
+//! Without that linker fragment - with the `pio` builder, or when built as a
+//! component of an ESP-IDF CMake project - the sections would end up as orphans
+//! wherever the linker puts them, so creating an entry fails the build instead.
+
+/// An entry of the registry of the functions called before the Rust `main`
+/// function, as created by the [`pre_main`](crate::pre_main) attribute.
+#[repr(transparent)]
+pub struct PreMain(fn());
+
+impl PreMain {
+    #[doc(hidden)]
+    pub const fn new(f: fn()) -> Self {
+        if cfg!(esp_idf_sys_pre_main) {
+            Self(f)
+        } else {
+            panic!("`#[pre_main]` functions require the `native` builder of `esp-idf-sys`")
+        }
+    }
+}
+
+/// Call the registered `#[pre_main]` functions, in the order of their priority
+#[cfg(all(esp_idf_sys_pre_main, any(feature = "binstart", feature = "libstart")))]
+pub(crate) fn run() {
+    extern "C" {
+        static _esp_idf_sys_pre_main_start: u8;
+        static _esp_idf_sys_pre_main_end: u8;
+    }
+
+    unsafe {
+        let start = core::ptr::addr_of!(_esp_idf_sys_pre_main_start) as *const PreMain;
+        let end = core::ptr::addr_of!(_esp_idf_sys_pre_main_end) as *const PreMain;
+
+        for entry in core::slice::from_raw_parts(start, end.offset_from(start) as usize) {
+            (entry.0)();
+        }
+    }
+}
+
+/// Without the linker fragment of the `main` component there is no registry (and
+/// no `#[pre_main]` functions)
+#[cfg(all(
+    not(esp_idf_sys_pre_main),
+    any(feature = "binstart", feature = "libstart")
+))]
+pub(crate) fn run() {}
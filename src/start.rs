@@ -38,8 +38,9 @@ extern "Rust" {
 
 #[no_mangle]
 pub extern "C" fn app_main() {
-    // Make sure the ESP-IDF patches implemented in Rust are linked to the
-    // final executable
+    // Make sure the ESP-IDF patches implemented in Rust are linked to the final
+    // executable with the builds which do not pass them to the linker as
+    // `--undefined` arguments (see `link_patches`)
     crate::link_patches();
 
     // Bind the C standard streams to their POSIX file descriptors (0, 1 and 2),
@@ -48,11 +49,343 @@ pub extern "C" fn app_main() {
     // `println!` et al.); see the documentation of this function for details
     crate::restore_posix_stdio_fds();
 
-    unsafe {
-        #[cfg(all(feature = "std", feature = "binstart"))]
-        main(0, &[core::ptr::null()] as *const *const u8);
+    // Run the initialization of the crates which registered `#[pre_main]` functions,
+    // so that it is done before - and without the help of - the Rust main function
+    crate::pre_main::run();
 
-        #[cfg(any(all(not(feature = "std"), feature = "binstart"), feature = "libstart"))]
-        main();
+    // Run the Rust main function on a task of its own, if configured so with
+    // `esp_idf_sys_main_task_*`, as the stack size, priority and core of the ESP-IDF
+    // main task - which runs `app_main` - can only be changed via sdkconfig
+    #[cfg(esp_idf_sys_main_task)]
+    main_task::spawn();
+
+    #[cfg(not(esp_idf_sys_main_task))]
+    run_main();
+}
+
+fn run_main() {
+    #[cfg(all(feature = "std", feature = "binstart"))]
+    let code = main_args::with_args(|argc, argv| unsafe { main(argc, argv) });
+
+    #[cfg(any(all(not(feature = "std"), feature = "binstart"), feature = "libstart"))]
+    let code = {
+        unsafe { main() };
+        0
+    };
+
+    main_exit::exit(code);
+}
+
+pub(crate) mod main_exit {
+    use core::ffi::{c_int, CStr};
+    use core::sync::atomic::{AtomicPtr, AtomicU8, Ordering};
+
+    use crate::*;
+
+    /// What the `binstart`/`libstart` glue does once the Rust main function
+    /// returns; see [`set_main_exit_action`].
+    ///
+    /// The default is `Return`, unless configured otherwise with the
+    /// `esp_idf_sys_main_exit` build option.
+    #[derive(Copy, Clone, Debug)]
+    pub enum MainExitAction {
+        /// Return, i.e. delete the task the main function ran on, leaving the
+        /// other tasks running
+        Return,
+        /// Log the exit code and restart the chip with `esp_restart`
+        Restart,
+        /// Log the exit code and enter deep sleep with `esp_deep_sleep_start`;
+        /// the wakeup sources must have been configured beforehand
+        DeepSleep,
+        /// Log the exit code and block the task the main function ran on forever
+        Spin,
+        /// Call the hook with the exit code, then return
+        Hook(fn(isize)),
+    }
+
+    /// Sets what happens once the Rust main function returns, with its exit code:
+    /// `0`, or - for a `main` returning `Result` - `1` if it failed.
+    pub fn set_main_exit_action(action: MainExitAction) {
+        let action = match action {
+            MainExitAction::Return => ACTION_RETURN,
+            MainExitAction::Restart => ACTION_RESTART,
+            MainExitAction::DeepSleep => ACTION_DEEP_SLEEP,
+            MainExitAction::Spin => ACTION_SPIN,
+            MainExitAction::Hook(hook) => {
+                HOOK.store(hook as *mut _, Ordering::Release);
+                ACTION_HOOK
+            }
+        };
+
+        ACTION.store(action, Ordering::Release);
+    }
+
+    /// Gets what happens once the Rust main function returns; see
+    /// [`set_main_exit_action`].
+    pub fn main_exit_action() -> MainExitAction {
+        match ACTION.load(Ordering::Acquire) {
+            ACTION_RESTART => MainExitAction::Restart,
+            ACTION_DEEP_SLEEP => MainExitAction::DeepSleep,
+            ACTION_SPIN => MainExitAction::Spin,
+            ACTION_HOOK => MainExitAction::Hook(unsafe {
+                core::mem::transmute::<*mut (), fn(isize)>(HOOK.load(Ordering::Acquire))
+            }),
+            _ => MainExitAction::Return,
+        }
+    }
+
+    const ACTION_RETURN: u8 = 0;
+    const ACTION_RESTART: u8 = 1;
+    const ACTION_DEEP_SLEEP: u8 = 2;
+    const ACTION_SPIN: u8 = 3;
+    const ACTION_HOOK: u8 = 4;
+
+    const DEFAULT: u8 = if cfg!(esp_idf_sys_main_exit = "restart") {
+        ACTION_RESTART
+    } else if cfg!(esp_idf_sys_main_exit = "deep_sleep") {
+        ACTION_DEEP_SLEEP
+    } else if cfg!(esp_idf_sys_main_exit = "spin") {
+        ACTION_SPIN
+    } else {
+        ACTION_RETURN
+    };
+
+    static ACTION: AtomicU8 = AtomicU8::new(DEFAULT);
+    static HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+    const TAG: &CStr = c"main";
+
+    pub(super) fn exit(code: isize) {
+        match main_exit_action() {
+            MainExitAction::Return => (),
+            MainExitAction::Restart => {
+                log(code, c"restarting");
+                unsafe { esp_restart() }
+            }
+            MainExitAction::DeepSleep => {
+                log(code, c"entering deep sleep");
+                unsafe { esp_deep_sleep_start() }
+            }
+            MainExitAction::Spin => {
+                log(code, c"halting");
+                loop {
+                    unsafe { vTaskDelay(TickType_t::MAX) };
+                }
+            }
+            MainExitAction::Hook(hook) => hook(code),
+        }
+    }
+
+    fn log(code: isize, action: &CStr) {
+        let (level, letter) = if code == 0 {
+            (esp_log_level_t_ESP_LOG_INFO, b'I')
+        } else {
+            (esp_log_level_t_ESP_LOG_ERROR, b'E')
+        };
+
+        unsafe {
+            esp_log_write(
+                level,
+                TAG.as_ptr(),
+                c"%c (%u) %s: Main returned with exit code %d, %s\n".as_ptr(),
+                letter as c_int,
+                esp_log_timestamp(),
+                TAG.as_ptr(),
+                code as c_int,
+                action.as_ptr(),
+            );
+        }
+    }
+}
+
+#[cfg(all(feature = "std", feature = "binstart"))]
+mod main_args {
+    /// Call `f` with the `argc` and `argv` of the Rust main function, as returned
+    /// by `std::env::args()`
+    #[cfg(not(esp_idf_sys_main_args))]
+    pub(super) fn with_args<R>(f: impl FnOnce(isize, *const *const u8) -> R) -> R {
+        f(0, [core::ptr::null()].as_ptr())
+    }
+
+    /// Call `f` with the `argc` and `argv` of the Rust main function, as returned
+    /// by `std::env::args()`: the program name `main`, followed by the arguments
+    /// stored in NVS - if configured and present - or by the `MAIN_ARGS`
+    #[cfg(esp_idf_sys_main_args)]
+    pub(super) fn with_args<R>(f: impl FnOnce(isize, *const *const u8) -> R) -> R {
+        #[cfg(esp_idf_comp_nvs_flash_enabled)]
+        if let Some((namespace, key)) = MAIN_ARGS_NVS {
+            if let Some(args) = unsafe { nvs::NvsArgs::read(namespace, key) } {
+                // The arguments are freed once the main function returns
+                return f(args.argc as _, args.argv);
+            }
+        }
+
+        let mut argv = [core::ptr::null(); MAIN_ARGS.len() + 2];
+
+        argv[0] = PROGRAM.as_ptr() as *const u8;
+        for (arg, main_arg) in argv[1..].iter_mut().zip(MAIN_ARGS) {
+            *arg = main_arg.as_ptr() as *const u8;
+        }
+
+        f((MAIN_ARGS.len() + 1) as _, argv.as_ptr())
+    }
+
+    #[cfg(esp_idf_sys_main_args)]
+    use config::*;
+
+    #[cfg(esp_idf_sys_main_args)]
+    #[allow(dead_code)]
+    mod config {
+        include!(concat!(env!("OUT_DIR"), "/main_args.rs"));
+    }
+
+    /// The program name, i.e. the first argument
+    #[cfg(esp_idf_sys_main_args)]
+    const PROGRAM: &core::ffi::CStr = c"main";
+
+    #[cfg(all(esp_idf_sys_main_args, esp_idf_comp_nvs_flash_enabled))]
+    mod nvs {
+        use core::ffi::{c_char, CStr};
+        use core::ptr;
+
+        use crate::*;
+
+        /// The arguments read from an NVS string, split in place at whitespace
+        pub(super) struct NvsArgs {
+            buf: *mut c_char,
+            pub(super) argv: *const *const u8,
+            pub(super) argc: usize,
+        }
+
+        impl NvsArgs {
+            pub(super) unsafe fn read(namespace: &CStr, key: &CStr) -> Option<Self> {
+                // NVS might not be initialized yet. If it cannot be initialized as-is,
+                // leave the erasing of the partition - as usually done in this case -
+                // to the application, and fall back to the compile-time arguments
+                if nvs_flash_init() != ESP_OK {
+                    return None;
+                }
+
+                let mut handle: nvs_handle_t = 0;
+                if nvs_open(
+                    namespace.as_ptr(),
+                    nvs_open_mode_t_NVS_READONLY,
+                    &mut handle,
+                ) != ESP_OK
+                {
+                    return None;
+                }
+
+                let mut len: size_t = 0;
+                let mut buf = ptr::null_mut();
+
+                if nvs_get_str(handle, key.as_ptr(), ptr::null_mut(), &mut len) == ESP_OK {
+                    buf = malloc(len) as *mut c_char;
+
+                    if !buf.is_null() && nvs_get_str(handle, key.as_ptr(), buf, &mut len) != ESP_OK
+                    {
+                        free(buf as *mut _);
+                        buf = ptr::null_mut();
+                    }
+                }
+
+                nvs_close(handle);
+
+                if buf.is_null() {
+                    None
+                } else {
+                    Self::split(buf)
+                }
+            }
+
+            unsafe fn split(buf: *mut c_char) -> Option<Self> {
+                let args = core::slice::from_raw_parts_mut(buf as *mut u8, strlen(buf) as usize);
+                let count = args
+                    .split(u8::is_ascii_whitespace)
+                    .filter(|arg| !arg.is_empty())
+                    .count();
+
+                let argv = malloc(((count + 2) * core::mem::size_of::<*const u8>()) as _)
+                    as *mut *const u8;
+                if argv.is_null() {
+                    free(buf as *mut _);
+                    return None;
+                }
+
+                *argv = super::PROGRAM.as_ptr() as *const u8;
+
+                let mut argc = 1;
+                let mut in_arg = false;
+
+                // The buffer is NUL-terminated, i.e. so is the last argument
+                for (index, byte) in args.iter_mut().enumerate() {
+                    if byte.is_ascii_whitespace() {
+                        *byte = 0;
+                        in_arg = false;
+                    } else if !in_arg {
+                        in_arg = true;
+                        *argv.add(argc) = (buf as *const u8).add(index);
+                        argc += 1;
+                    }
+                }
+
+                *argv.add(argc) = ptr::null();
+
+                Some(Self { buf, argv, argc })
+            }
+        }
+
+        impl Drop for NvsArgs {
+            fn drop(&mut self) {
+                unsafe {
+                    free(self.argv as *mut _);
+                    free(self.buf as *mut _);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(esp_idf_sys_main_task)]
+pub(crate) mod main_task {
+    use core::ffi::c_void;
+    use core::ptr;
+
+    use crate::*;
+
+    include!(concat!(env!("OUT_DIR"), "/main_task.rs"));
+
+    /// `tskNO_AFFINITY`, which is defined with a cast since ESP-IDF v5.0,
+    /// and is thus not part of the bindings
+    const NO_AFFINITY: BaseType_t = 0x7fffffff;
+
+    /// Spawn the task running the Rust main function; `app_main` - and with it
+    /// the ESP-IDF main task - can then return, freeing its stack
+    pub(super) fn spawn() {
+        let created = unsafe {
+            xTaskCreatePinnedToCore(
+                Some(task),
+                c"main".as_ptr(),
+                MAIN_TASK_STACK_SIZE as _,
+                ptr::null_mut(),
+                MAIN_TASK_PRIORITY as _,
+                ptr::null_mut(),
+                MAIN_TASK_CORE.map_or(NO_AFFINITY, |core| core as _),
+            )
+        };
+
+        // `pdPASS`
+        if created != 1 {
+            panic!("Failed to spawn the main task with a stack of {MAIN_TASK_STACK_SIZE} bytes");
+        }
+    }
+
+    extern "C" fn task(_arg: *mut c_void) {
+        super::run_main();
+
+        // FreeRTOS tasks must not return
+        unsafe {
+            vTaskDelete(ptr::null_mut());
+        }
     }
 }
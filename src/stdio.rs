@@ -20,6 +20,15 @@
 //! component is compiled in (`CONFIG_VFS_SUPPORT_IO`). Without it, ESP-IDF
 //! initializes the standard streams with the stock `__sinit` of the C library,
 //! which does bind them to descriptors 0, 1 and 2 natively.
+//!
+//! Once the POSIX binding is in place, descriptors 0, 1 and 2 can also be
+//! re-pointed to other VFS devices or files with [`redirect_stdio`].
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+use core::ffi::CStr;
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+use crate::{EspErrno, ENAMETOOLONG, ENOTSUP};
 
 /// Restores the POSIX binding between the C standard streams and file
 /// descriptors 0, 1 and 2, by re-creating the standard streams in a way where
@@ -27,19 +36,29 @@
 ///
 /// Returns `true` if the binding is in place when the function returns
 /// (either because it was already in place, or because it was successfully
-/// restored), and `false` otherwise, in which case the standard streams are in
-/// the same state as if the function had not been called at all.
+/// restored), and `false` otherwise, in which case the standard streams are -
+/// with ESP-IDF v5.3+ - in the same state as if the function had not been
+/// called at all.
 ///
 /// The function is idempotent. It is called automatically from the `app_main`
 /// glue of the `binstart`/`libstart` features, so calling it explicitly is
 /// only necessary with a custom `app_main`.
 ///
-/// On ESP-IDF versions older than v5.3 the restoration is not attempted (the
-/// function only reports whether the binding happens to be in place): the
-/// console of those versions does not refcount its open/close calls, which
-/// the re-creation of the standard streams relies on. For the older versions,
-/// the `CONFIG_ESP_CONSOLE_SECONDARY_NONE=y` sdkconfig setting is a build-time
-/// alternative, as it results in descriptors 1 and 2 landing on the console.
+/// On ESP-IDF v5.1 and v5.2, the console does not refcount its open/close
+/// calls, which the re-creation of the standard streams on top of
+/// `/dev/console` relies on. With these versions, the standard streams are
+/// instead re-created on top of the device of the *primary* console
+/// (`/dev/uart/<n>`, `/dev/usbserjtag` or `/dev/cdcacm`) directly, so the
+/// output of the standard streams no longer reaches the secondary console, if
+/// there is one. As descriptors 0, 1 and 2 are closed in the process, the
+/// function must be called before anything else opens a descriptor with these
+/// versions. The `CONFIG_ESP_CONSOLE_SECONDARY_NONE=y` sdkconfig setting
+/// remains a build-time alternative, as it results in descriptors 1 and 2
+/// landing on the console.
+///
+/// On older ESP-IDF versions, and without a console device
+/// (`CONFIG_ESP_CONSOLE_NONE=y`), the restoration is not attempted; the
+/// function only reports whether the binding happens to be in place.
 ///
 /// NOTE: the standard streams are torn down and re-created while the function
 /// runs, so it must be called *before* any other thread might be using them -
@@ -56,19 +75,25 @@ pub fn restore_posix_stdio_fds() -> bool {
             return true;
         }
 
-        #[cfg(all(
-            esp_idf_comp_vfs_enabled,
-            esp_idf_vfs_support_io,
-            esp_idf_version_at_least_5_3_0
+        #[cfg(any(
+            all(
+                esp_idf_comp_vfs_enabled,
+                esp_idf_vfs_support_io,
+                esp_idf_version_at_least_5_3_0
+            ),
+            esp_idf_sys_stdio_legacy_console
         ))]
         {
             imp::restore()
         }
 
-        #[cfg(not(all(
-            esp_idf_comp_vfs_enabled,
-            esp_idf_vfs_support_io,
-            esp_idf_version_at_least_5_3_0
+        #[cfg(not(any(
+            all(
+                esp_idf_comp_vfs_enabled,
+                esp_idf_vfs_support_io,
+                esp_idf_version_at_least_5_3_0
+            ),
+            esp_idf_sys_stdio_legacy_console
         )))]
         {
             false
@@ -76,35 +101,369 @@ pub fn restore_posix_stdio_fds() -> bool {
     }
 }
 
+/// Redirects the standard streams - i.e. file descriptors 0, 1 and 2 - to the
+/// VFS paths `stdin`, `stdout` and `stderr`, e.g. a specific UART
+/// (`/dev/uart/1`), the USB-Serial-JTAG device (`/dev/usbserjtag`), a TinyUSB
+/// CDC-ACM port (`/dev/cdcacm`) or a file (`/spiffs/log.txt`).
+///
+/// `stdin` is opened for reading; `stdout` and `stderr` are opened for
+/// appending, and created if they are files which do not exist yet.
+///
+/// Returns a guard which redirects the standard streams back to the paths
+/// they were bound to before, when dropped. Nested redirections should be
+/// dropped in the reverse order of their creation.
+///
+/// The redirection first establishes the POSIX binding of the standard
+/// streams with [`restore_posix_stdio_fds`], and fails with `ENOTSUP` if that
+/// is not possible, or if a path is `/dev/console` with ESP-IDF < v5.3 (use
+/// the device of the console directly instead). It fails with `EBUSY` if
+/// another redirection is in progress, with `ENAMETOOLONG` if a path is longer
+/// than [`STDIO_PATH_MAX_LEN`], and with the `errno` of `open` if a path cannot
+/// be opened. The standard streams are left unchanged in all of these cases; should
+/// a path only fail to open once the descriptors are being switched, they are
+/// switched back to the paths they were bound to before.
+///
+/// NOTE: both the C standard streams and the Rust Standard Library keep
+/// writing to descriptors 1 and 2, so their output follows the redirection,
+/// but output buffered in them before the redirection might end up on either
+/// side of it. Output written by other threads *while* the descriptors are
+/// being switched might be lost.
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+pub fn redirect_stdio(
+    stdin: &CStr,
+    stdout: &CStr,
+    stderr: &CStr,
+) -> Result<StdioRedirection, EspErrno> {
+    let paths = [
+        StdioPath::new(stdin)?,
+        StdioPath::new(stdout)?,
+        StdioPath::new(stderr)?,
+    ];
+
+    // The console of ESP-IDF < v5.3 cannot be opened on a specific descriptor;
+    // see `restore_posix_stdio_fds`
+    #[cfg(not(esp_idf_version_at_least_5_3_0))]
+    if paths.iter().any(|path| path.as_c_str() == c"/dev/console") {
+        return Err(errno(ENOTSUP));
+    }
+
+    if !restore_posix_stdio_fds() {
+        return Err(errno(ENOTSUP));
+    }
+
+    let previous = redirect::redirect(paths)?;
+
+    Ok(StdioRedirection { previous })
+}
+
+/// The maximum length of a path passed to [`redirect_stdio`], without the
+/// terminating NUL
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+pub const STDIO_PATH_MAX_LEN: usize = 127;
+
+/// A guard returned by [`redirect_stdio`], which redirects the standard
+/// streams back to their previous paths when dropped
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+#[must_use = "dropping the guard immediately undoes the redirection"]
+pub struct StdioRedirection {
+    previous: [StdioPath; 3],
+}
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+impl StdioRedirection {
+    /// Keeps the redirection in place for the rest of the program.
+    pub fn leak(self) {
+        core::mem::forget(self);
+    }
+}
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+impl Drop for StdioRedirection {
+    fn drop(&mut self) {
+        // Nothing sensible to do on failure; the streams stay where they are
+        let _ = redirect::redirect(self.previous.clone());
+    }
+}
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+impl core::fmt::Debug for StdioRedirection {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StdioRedirection")
+            .field("previous", &self.previous)
+            .finish()
+    }
+}
+
+/// A NUL-terminated VFS path of at most [`STDIO_PATH_MAX_LEN`] bytes
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+#[derive(Clone)]
+struct StdioPath([u8; STDIO_PATH_MAX_LEN + 1]);
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+impl StdioPath {
+    const CONSOLE: Self = Self::from_bytes(imp::STDIO_DEVICE.to_bytes());
+
+    fn new(path: &CStr) -> Result<Self, EspErrno> {
+        let bytes = path.to_bytes();
+        if bytes.len() > STDIO_PATH_MAX_LEN {
+            return Err(errno(ENAMETOOLONG));
+        }
+
+        Ok(Self::from_bytes(bytes))
+    }
+
+    const fn from_bytes(bytes: &[u8]) -> Self {
+        let mut buf = [0; STDIO_PATH_MAX_LEN + 1];
+
+        let mut index = 0;
+        while index < bytes.len() {
+            buf[index] = bytes[index];
+            index += 1;
+        }
+
+        Self(buf)
+    }
+
+    fn as_c_str(&self) -> &CStr {
+        // Always NUL-terminated, as the last byte is never written
+        CStr::from_bytes_until_nul(&self.0).unwrap()
+    }
+}
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+impl core::fmt::Debug for StdioPath {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_c_str(), f)
+    }
+}
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+fn errno(errno: u32) -> EspErrno {
+    EspErrno::from(errno as _).unwrap()
+}
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+mod redirect {
+    use core::cell::UnsafeCell;
+    use core::ffi::c_int;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::*;
+
+    use super::imp::{self, Placeholders};
+    use super::{errno, StdioPath};
+
+    /// The paths the standard streams are currently redirected to
+    struct Current {
+        busy: AtomicBool,
+        paths: UnsafeCell<[StdioPath; 3]>,
+    }
+
+    // SAFETY: `paths` is only accessed while holding `busy`
+    unsafe impl Sync for Current {}
+
+    static CURRENT: Current = Current {
+        busy: AtomicBool::new(false),
+        paths: UnsafeCell::new([StdioPath::CONSOLE, StdioPath::CONSOLE, StdioPath::CONSOLE]),
+    };
+
+    /// Redirects descriptors 0, 1 and 2 to `paths`, returning the paths they
+    /// were redirected to before
+    pub(super) fn redirect(paths: [StdioPath; 3]) -> Result<[StdioPath; 3], EspErrno> {
+        locked(|current| unsafe {
+            reopen(&paths, Some(current)).map(|()| core::mem::replace(current, paths))
+        })
+    }
+
+    /// Re-opens the current paths of descriptors 0, 1 and 2, undoing an
+    /// [`intercept`]
+    pub(super) fn reset() -> Result<(), EspErrno> {
+        locked(|current| unsafe { reopen(current, None) })
+    }
+
+    /// Opens the current paths of descriptors 1 and 2 once more, and
+    /// re-points descriptors 1 and 2 to the descriptors `register` returns
+    /// for these - presumably forwarding to them
+    pub(super) fn intercept(
+        register: impl Fn(c_int) -> Option<c_int>,
+    ) -> Result<[c_int; 2], EspErrno> {
+        const OUTPUT: c_int = (O_WRONLY | O_CREAT | O_APPEND) as c_int;
+
+        locked(|current| unsafe {
+            let mut targets: [c_int; 2] = [-1; 2];
+            for (index, path) in current[1..].iter().enumerate() {
+                targets[index] = open(path.as_c_str().as_ptr(), OUTPUT, 0o666 as c_int);
+
+                if targets[index] < 0 {
+                    let err = EspErrno::last().unwrap_or_else(|| errno(EIO));
+                    close_all(&targets);
+
+                    return Err(err);
+                }
+            }
+
+            imp::flush();
+
+            for (index, target) in targets.iter().enumerate() {
+                let fd = index as c_int + 1;
+
+                close(fd);
+
+                // Descriptor 0 is taken, so `fd` is the lowest free one
+                if register(*target) != Some(fd) {
+                    // (the descriptors registered so far are closed with their entries)
+                    close_all(&targets[index..]);
+                    let _ = reopen(current, None);
+
+                    return Err(errno(ENOMEM));
+                }
+            }
+
+            Ok(targets)
+        })
+    }
+
+    fn locked<R>(
+        f: impl FnOnce(&mut [StdioPath; 3]) -> Result<R, EspErrno>,
+    ) -> Result<R, EspErrno> {
+        if CURRENT
+            .busy
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(errno(EBUSY));
+        }
+
+        let result = f(unsafe { &mut *CURRENT.paths.get() });
+
+        CURRENT.busy.store(false, Ordering::Release);
+
+        result
+    }
+
+    /// The placeholder technique of `restore_posix_stdio_fds`, applied to
+    /// arbitrary paths: descriptors 0, 1 and 2 are closed and claimed by
+    /// placeholder entries, which are then released one by one, each right
+    /// before the path of the descriptor is opened - and thus assigned the
+    /// lowest free descriptor, i.e. the one just released
+    ///
+    /// Should a path fail to open once the descriptors are being switched, the
+    /// descriptors are switched back to `previous`, if given
+    unsafe fn reopen(
+        paths: &[StdioPath; 3],
+        previous: Option<&[StdioPath; 3]>,
+    ) -> Result<(), EspErrno> {
+        const OUTPUT: c_int = (O_WRONLY | O_CREAT | O_APPEND) as c_int;
+        let flags = [O_RDONLY as c_int, OUTPUT, OUTPUT];
+
+        // Open every path once up-front, on descriptors >= 3:
+        // - fail early, with the standard streams untouched, if a path cannot be opened
+        // - keep the devices which open backing devices of their own (like
+        //   `/dev/console`) latched, so that they open nothing but the one
+        //   descriptor when opened again below
+        let mut probes: [c_int; 3] = [-1; 3];
+        for (index, path) in paths.iter().enumerate() {
+            probes[index] = open(path.as_c_str().as_ptr(), flags[index], 0o666 as c_int);
+
+            if probes[index] < 0 {
+                let err = EspErrno::last().unwrap_or_else(|| errno(EIO));
+                close_all(&probes);
+
+                return Err(err);
+            }
+        }
+
+        let Some(placeholders) = Placeholders::register() else {
+            close_all(&probes);
+            return Err(errno(ENOMEM));
+        };
+
+        imp::flush();
+
+        for fd in 0..3 {
+            close(fd);
+        }
+
+        placeholders.claim();
+
+        let mut result = Ok(());
+        for (index, path) in paths.iter().enumerate() {
+            let fd = index as c_int;
+
+            placeholders.release(fd);
+
+            if open(path.as_c_str().as_ptr(), flags[index], 0o666 as c_int) != fd && result.is_ok()
+            {
+                result = Err(EspErrno::last().unwrap_or_else(|| errno(EIO)));
+            }
+        }
+
+        drop(placeholders);
+        close_all(&probes);
+
+        if let (Err(_), Some(previous)) = (&result, previous) {
+            // Nothing sensible to do should this fail as well
+            let _ = reopen(previous, None);
+        }
+
+        result
+    }
+
+    unsafe fn close_all(fds: &[c_int]) {
+        for fd in fds.iter().copied().filter(|fd| *fd >= 0) {
+            close(fd);
+        }
+    }
+}
+
+mod tee;
+
+pub use tee::*;
+
 mod imp {
-    #[cfg(all(
-        esp_idf_comp_vfs_enabled,
-        esp_idf_vfs_support_io,
-        esp_idf_version_at_least_5_3_0
-    ))]
+    #[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
     use core::ffi::c_int;
-    #[cfg(all(
-        esp_idf_comp_vfs_enabled,
-        esp_idf_vfs_support_io,
-        esp_idf_version_at_least_5_3_0
-    ))]
+    #[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
     use core::ptr;
 
     use crate::*;
 
-    #[cfg(all(
-        esp_idf_comp_vfs_enabled,
-        esp_idf_vfs_support_io,
-        esp_idf_version_at_least_5_3_0
-    ))]
-    const CONSOLE: &core::ffi::CStr = c"/dev/console";
+    #[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+    use core::ffi::CStr;
+
+    #[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+    const CONSOLE: &CStr = c"/dev/console";
+
+    /// The device of the primary console, which backs `/dev/console`
+    #[cfg(all(esp_idf_sys_stdio_legacy_console, esp_idf_esp_console_uart))]
+    const PRIMARY_CONSOLE: &CStr = match CONFIG_ESP_CONSOLE_UART_NUM {
+        0 => c"/dev/uart/0",
+        1 => c"/dev/uart/1",
+        _ => c"/dev/uart/2",
+    };
+    #[cfg(all(esp_idf_sys_stdio_legacy_console, esp_idf_esp_console_usb_serial_jtag))]
+    const PRIMARY_CONSOLE: &CStr = c"/dev/usbserjtag";
+    #[cfg(all(esp_idf_sys_stdio_legacy_console, esp_idf_esp_console_usb_cdc))]
+    const PRIMARY_CONSOLE: &CStr = c"/dev/cdcacm";
+
+    /// The device the standard streams are bound to by [`restore`]
+    #[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+    pub(super) const STDIO_DEVICE: &CStr = {
+        #[cfg(not(esp_idf_sys_stdio_legacy_console))]
+        let device = CONSOLE;
+        #[cfg(esp_idf_sys_stdio_legacy_console)]
+        let device = PRIMARY_CONSOLE;
+
+        device
+    };
 
     /// Returns `true` if the standard streams are bound to descriptors 0, 1
-    /// and 2
+    /// and 2 (under picolibc, `stderr` shares the stream of `stdout`)
     pub(super) unsafe fn bound() -> bool {
         matches!(
             streams(),
-            Some((si, so, se)) if fileno(si) == 0 && fileno(so) == 1 && fileno(se) == 2
+            Some((si, so, se))
+                if fileno(si) == 0 && fileno(so) == 1 && (se == so || fileno(se) == 2)
         )
     }
 
@@ -130,16 +489,9 @@ mod imp {
 
         // Claim descriptors 0-2 with placeholder entries, so that the backing
         // devices of the console land on descriptors >= 3 when it re-opens them
-        let mut vfs_id: esp_vfs_id_t = -1;
-        let vfs = core::mem::zeroed::<esp_vfs_t>();
-        let placeholders = esp_vfs_register_with_id(&vfs, ptr::null_mut(), &mut vfs_id) == ESP_OK;
-        if placeholders {
-            for _ in 0..3 {
-                let mut placeholder: c_int = -1;
-                if esp_vfs_register_fd(vfs_id, &mut placeholder) != ESP_OK {
-                    break;
-                }
-            }
+        let placeholders = Placeholders::register();
+        if let Some(placeholders) = &placeholders {
+            placeholders.claim();
         }
 
         // Open the console once: this makes it re-open - and re-latch - the
@@ -149,11 +501,8 @@ mod imp {
         // Release the placeholders and re-run the stdio initialization of
         // ESP-IDF: the standard streams now claim the freed descriptors
         // 0, 1 and 2, in that order
-        if placeholders {
-            // (also releases the placeholder descriptors)
-            esp_vfs_unregister_with_id(vfs_id);
-        }
-        init_global_stdio();
+        drop(placeholders);
+        init_global_stdio(CONSOLE);
         if probe >= 0 {
             close(probe);
         }
@@ -163,6 +512,126 @@ mod imp {
         matches!(streams(), Some((_, so, _)) if fileno(so) == 1)
     }
 
+    #[cfg(esp_idf_sys_stdio_legacy_console)]
+    pub(super) unsafe fn restore() -> bool {
+        let Some((si, so, se)) = streams() else {
+            return false;
+        };
+
+        // Identify the backing devices of the console while the standard streams
+        // still hold descriptors 0-2, so that the probes land elsewhere
+        let mut devices = [None; BACKING_DEVICES.len()];
+        for (device, path) in devices.iter_mut().zip(BACKING_DEVICES) {
+            let fd = open(path.as_ptr(), O_RDONLY as c_int);
+            if fd >= 0 {
+                *device = char_device(fd);
+                close(fd);
+            }
+        }
+
+        // Close the standard streams. The console of these versions latches the
+        // descriptors of its backing devices anew on every open, and closes only
+        // the latest ones, so the ones opened for `stdin` - usually descriptors 0
+        // and 1 (and 2 without a secondary console) - stay open and are leaked
+        fclose(si);
+        fclose(so);
+        fclose(se);
+
+        // Close the leaked descriptors, so that descriptors 0-2 become free. Any
+        // of them which is not open on a backing device of the console belongs
+        // to someone else and stays open, in which case the binding cannot be
+        // restored
+        for fd in 0..3 {
+            if char_device(fd).is_some_and(|leaked| devices.contains(&Some(leaked))) {
+                close(fd);
+            }
+        }
+
+        // Re-run the stdio initialization of ESP-IDF on top of the primary
+        // console device, bypassing `/dev/console`: opening the device claims
+        // just one descriptor, so the standard streams claim descriptors 0, 1
+        // and 2, in that order
+        init_global_stdio(PRIMARY_CONSOLE);
+
+        finish();
+
+        bound()
+    }
+
+    /// The devices backing the console
+    #[cfg(esp_idf_sys_stdio_legacy_console)]
+    const BACKING_DEVICES: &[&CStr] = &[
+        PRIMARY_CONSOLE,
+        #[cfg(esp_idf_esp_console_secondary_usb_serial_jtag)]
+        c"/dev/usbserjtag",
+    ];
+
+    /// Returns the identity - as reported by `fstat` - of the character device
+    /// descriptor `fd` is open on, or `None` if `fd` is not open on one
+    #[cfg(esp_idf_sys_stdio_legacy_console)]
+    unsafe fn char_device(fd: c_int) -> Option<(dev_t, dev_t, ino_t)> {
+        let mut st = core::mem::MaybeUninit::<stat>::zeroed();
+        if fstat(fd, st.as_mut_ptr()) != 0 {
+            return None;
+        }
+
+        let st = st.assume_init();
+
+        (st.st_mode & S_IFMT == S_IFCHR).then_some((st.st_dev, st.st_rdev, st.st_ino))
+    }
+
+    /// Placeholder entries of an empty VFS, used to claim the lowest free
+    /// descriptors
+    #[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+    pub(super) struct Placeholders(esp_vfs_id_t);
+
+    #[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+    impl Placeholders {
+        /// Registers the empty VFS, without claiming any descriptors yet
+        pub(super) unsafe fn register() -> Option<Self> {
+            let mut vfs_id: esp_vfs_id_t = -1;
+            let vfs = core::mem::zeroed::<esp_vfs_t>();
+
+            (esp_vfs_register_with_id(&vfs, ptr::null_mut(), &mut vfs_id) == ESP_OK)
+                .then_some(Self(vfs_id))
+        }
+
+        /// Claims the three lowest free descriptors - i.e. descriptors 0, 1
+        /// and 2, if these are free
+        pub(super) unsafe fn claim(&self) {
+            for _ in 0..3 {
+                let mut placeholder: c_int = -1;
+                if esp_vfs_register_fd(self.0, &mut placeholder) != ESP_OK {
+                    break;
+                }
+            }
+        }
+
+        /// Releases the claimed descriptor `fd`
+        pub(super) unsafe fn release(&self, fd: c_int) {
+            esp_vfs_unregister_fd(self.0, fd);
+        }
+    }
+
+    #[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+    impl Drop for Placeholders {
+        fn drop(&mut self) {
+            // (also releases the claimed descriptors)
+            unsafe {
+                esp_vfs_unregister_with_id(self.0);
+            }
+        }
+    }
+
+    /// Flushes the output buffered in the C standard streams
+    #[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+    pub(super) unsafe fn flush() {
+        if let Some((_, so, se)) = streams() {
+            fflush(so);
+            fflush(se);
+        }
+    }
+
     #[cfg(not(esp_idf_libc_picolibc))]
     unsafe fn streams() -> Option<(*mut FILE, *mut FILE, *mut FILE)> {
         // The global reentrancy structure owns the standard streams; the reent
@@ -178,9 +647,14 @@ mod imp {
     }
 
     #[cfg(all(
-        esp_idf_comp_vfs_enabled,
-        esp_idf_vfs_support_io,
-        esp_idf_version_at_least_5_3_0,
+        any(
+            all(
+                esp_idf_comp_vfs_enabled,
+                esp_idf_vfs_support_io,
+                esp_idf_version_at_least_5_3_0
+            ),
+            esp_idf_sys_stdio_legacy_console
+        ),
         not(esp_idf_libc_picolibc)
     ))]
     unsafe fn finish() {
@@ -224,17 +698,22 @@ mod imp {
         esp_idf_version_at_least_5_3_0,
         esp_idf_version_at_least_5_5_0
     ))]
-    unsafe fn init_global_stdio() {
-        esp_libc_init_global_stdio(CONSOLE.as_ptr());
+    unsafe fn init_global_stdio(device: &CStr) {
+        esp_libc_init_global_stdio(device.as_ptr());
     }
 
     #[cfg(all(
-        esp_idf_comp_vfs_enabled,
-        esp_idf_vfs_support_io,
-        esp_idf_version_at_least_5_3_0,
+        any(
+            all(
+                esp_idf_comp_vfs_enabled,
+                esp_idf_vfs_support_io,
+                esp_idf_version_at_least_5_3_0
+            ),
+            esp_idf_sys_stdio_legacy_console
+        ),
         not(esp_idf_version_at_least_5_5_0)
     ))]
-    unsafe fn init_global_stdio() {
-        esp_newlib_init_global_stdio(CONSOLE.as_ptr());
+    unsafe fn init_global_stdio(device: &CStr) {
+        esp_newlib_init_global_stdio(device.as_ptr());
     }
 }
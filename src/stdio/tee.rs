@@ -0,0 +1,364 @@
+//! Duplicating the output of the standard streams and of the ESP-IDF logging
+//! into a sink provided by the application, e.g. a ring buffer, a file or a
+//! network socket, for remote log collection.
+
+use core::ffi::c_void;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use crate::vprintf::{esp_log_set_vprintf, VprintfLike};
+use crate::*;
+
+/// The source of the data passed to a [`StdioSink`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum StdioSource {
+    /// Written to descriptor 1, e.g. with `println!` or `printf`
+    Stdout,
+    /// Written to descriptor 2, e.g. with `eprintln!`
+    Stderr,
+    /// Output of the ESP-IDF logging (`ESP_LOGI` etc.)
+    Log,
+}
+
+/// A sink receiving a copy of the output of the standard streams and/or of the
+/// ESP-IDF logging; see [`tee_stdio`] and [`tee_esp_log`].
+///
+/// The sink is called from the writing thread, with the output in chunks as
+/// written (which are not necessarily complete lines). It must neither block
+/// for long nor write to the standard streams or log itself.
+pub type StdioSink = fn(StdioSource, &[u8]);
+
+/// The maximum length of an ESP-IDF log message passed to the sink by
+/// [`tee_esp_log`]; longer messages are truncated (for the sink only)
+pub const TEE_LOG_MESSAGE_MAX_LEN: usize = 255;
+
+/// Duplicates everything written to descriptors 1 and 2 into `sink`, in
+/// addition to the devices or files the descriptors are bound to.
+///
+/// As the ESP-IDF logging writes to `stdout` by default, the sink receives its
+/// output as well - unless [`tee_esp_log`] is active too, in which case the
+/// sink receives that output only once, as [`StdioSource::Log`].
+///
+/// Returns a guard which ends the duplication when dropped. Only one
+/// duplication can be active at a time; the function fails with `EBUSY`
+/// otherwise. It also fails - like [`redirect_stdio`] - if the POSIX binding
+/// of the standard streams cannot be established. Redirecting the standard
+/// streams with [`redirect_stdio`] ends the duplication.
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+pub fn tee_stdio(sink: StdioSink) -> Result<StdioTee, EspErrno> {
+    if !restore_posix_stdio_fds() {
+        return Err(super::errno(ENOTSUP));
+    }
+
+    if STDIO_SINK
+        .compare_exchange(
+            ptr::null_mut(),
+            sink as *mut _,
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        )
+        .is_err()
+    {
+        return Err(super::errno(EBUSY));
+    }
+
+    match unsafe { stdio::install() } {
+        Ok(tee) => Ok(tee),
+        Err(err) => {
+            STDIO_SINK.store(ptr::null_mut(), Ordering::Release);
+            Err(err)
+        }
+    }
+}
+
+/// A guard returned by [`tee_stdio`], which ends the duplication when dropped
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+#[derive(Debug)]
+#[must_use = "dropping the guard immediately ends the duplication"]
+pub struct StdioTee {
+    vfs_id: esp_vfs_id_t,
+}
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+impl StdioTee {
+    /// Keeps the duplication in place for the rest of the program.
+    pub fn leak(self) {
+        core::mem::forget(self);
+    }
+}
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+impl Drop for StdioTee {
+    fn drop(&mut self) {
+        // Re-open the paths of the standard streams on descriptors 0, 1 and 2,
+        // replacing the forwarding entries of descriptors 1 and 2
+        let _ = super::redirect::reset();
+
+        unsafe {
+            esp_vfs_unregister_with_id(self.vfs_id);
+        }
+
+        STDIO_SINK.store(ptr::null_mut(), Ordering::Release);
+    }
+}
+
+/// Duplicates the output of the ESP-IDF logging into `sink`, by hooking
+/// `esp_log_set_vprintf`. The output still reaches the previously installed
+/// `vprintf`-like function - by default `vprintf`, writing to `stdout`.
+///
+/// The sink receives every message formatted, in one piece of at most
+/// [`TEE_LOG_MESSAGE_MAX_LEN`] bytes.
+///
+/// Returns a guard which re-installs the previous `vprintf`-like function
+/// when dropped. Only one duplication can be active at a time; the function
+/// fails with `EBUSY` otherwise, and with `ENOMEM` if the thread-specific key
+/// marking the logging tasks cannot be created. Installing another
+/// `vprintf`-like function with `esp_log_set_vprintf` while the guard is alive
+/// ends the duplication.
+pub fn tee_esp_log(sink: StdioSink) -> Result<EspLogTee, EspErrno> {
+    if LOG_SINK
+        .compare_exchange(
+            ptr::null_mut(),
+            sink as *mut _,
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        )
+        .is_err()
+    {
+        return Err(EspErrno::from(EBUSY as _).unwrap());
+    }
+
+    if LOG_TASK_KEY.load(Ordering::Acquire) == 0 {
+        // (only the caller which has set the sink gets here, so the key is created once)
+        let mut key: pthread_key_t = 0;
+        if unsafe { pthread_key_create(&mut key, None) } != 0 {
+            LOG_SINK.store(ptr::null_mut(), Ordering::Release);
+            return Err(EspErrno::from(ENOMEM as _).unwrap());
+        }
+
+        LOG_TASK_KEY.store(key as usize + 1, Ordering::Release);
+    }
+
+    let previous = unsafe { esp_log_set_vprintf(Some(vprintf_hook::vprintf)) };
+
+    LOG_PREVIOUS.store(
+        previous.map_or(ptr::null_mut(), |previous| previous as *mut _),
+        Ordering::Release,
+    );
+
+    Ok(EspLogTee(()))
+}
+
+/// A guard returned by [`tee_esp_log`], which ends the duplication when dropped
+#[derive(Debug)]
+#[must_use = "dropping the guard immediately ends the duplication"]
+pub struct EspLogTee(());
+
+impl EspLogTee {
+    /// Keeps the duplication in place for the rest of the program.
+    pub fn leak(self) {
+        core::mem::forget(self);
+    }
+}
+
+impl Drop for EspLogTee {
+    fn drop(&mut self) {
+        let previous = LOG_PREVIOUS.load(Ordering::Acquire);
+
+        unsafe {
+            esp_log_set_vprintf(if previous.is_null() {
+                None
+            } else {
+                Some(core::mem::transmute::<*mut c_void, VprintfLike>(previous))
+            });
+        }
+
+        LOG_SINK.store(ptr::null_mut(), Ordering::Release);
+    }
+}
+
+static STDIO_SINK: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static LOG_SINK: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static LOG_PREVIOUS: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+/// The key (plus one, or `0` until [`tee_esp_log`] creates it) of the thread-specific
+/// marker of the tasks currently passing a log message on to the previous
+/// `vprintf`-like function, whose output the stdio duplication should not pass
+/// to the sink a second time
+static LOG_TASK_KEY: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the key of the thread-specific logging marker, if [`tee_esp_log`] has created it
+fn log_task_key() -> Option<pthread_key_t> {
+    let key = LOG_TASK_KEY.load(Ordering::Acquire);
+
+    (key != 0).then(|| (key - 1) as _)
+}
+
+fn sink(sink: &AtomicPtr<c_void>) -> Option<StdioSink> {
+    let sink = sink.load(Ordering::Acquire);
+
+    (!sink.is_null()).then(|| unsafe { core::mem::transmute::<*mut c_void, StdioSink>(sink) })
+}
+
+#[cfg(all(esp_idf_comp_vfs_enabled, esp_idf_vfs_support_io))]
+mod stdio {
+    use core::ffi::{c_int, c_void};
+    use core::ptr;
+    use core::sync::atomic::{AtomicI32, Ordering};
+
+    use crate::*;
+
+    use super::{log_task_key, sink, StdioSource, StdioTee, STDIO_SINK};
+
+    /// The descriptor `stdout` is forwarded to
+    static STDOUT_TARGET: AtomicI32 = AtomicI32::new(-1);
+
+    /// The operations table of a VFS: `esp_vfs_fs_ops_t` with ESP-IDF v5.4+, where
+    /// `esp_vfs_t` is the legacy one
+    #[cfg(esp_idf_version_at_least_5_4_0)]
+    type Ops = esp_vfs_fs_ops_t;
+    #[cfg(not(esp_idf_version_at_least_5_4_0))]
+    type Ops = esp_vfs_t;
+
+    // The operations are unions of their `_p` (context pointer) and plain flavors, which
+    // bindgen names `__bindgen_anon_N` by position. Make sure that the unions used below
+    // are the 1st (`write`), 7th (`close`) and 8th (`fstat`) operation of the table
+    const _: () = {
+        use core::mem::{offset_of, size_of};
+
+        let first = offset_of!(Ops, __bindgen_anon_1);
+        let op = size_of::<Option<unsafe extern "C" fn()>>();
+
+        assert!(offset_of!(Ops, __bindgen_anon_7) == first + 6 * op);
+        assert!(offset_of!(Ops, __bindgen_anon_8) == first + 7 * op);
+    };
+
+    pub(super) unsafe fn install() -> Result<StdioTee, EspErrno> {
+        // A VFS forwarding to the descriptors which are registered as its local ones
+        let mut ops = core::mem::zeroed::<Ops>();
+        ops.__bindgen_anon_1.write = Some(write);
+        ops.__bindgen_anon_7.close = Some(close);
+        ops.__bindgen_anon_8.fstat = Some(fstat);
+
+        let mut vfs_id: esp_vfs_id_t = -1;
+        esp!(register(&ops, &mut vfs_id)).map_err(|_| super::super::errno(ENOMEM))?;
+
+        let targets = super::super::redirect::intercept(|target| {
+            let mut fd: c_int = -1;
+
+            (esp_vfs_register_fd_with_local_fd(vfs_id, target, false, &mut fd) == ESP_OK)
+                .then_some(fd)
+        });
+
+        match targets {
+            Ok([stdout_target, _]) => {
+                STDOUT_TARGET.store(stdout_target, Ordering::Release);
+                Ok(StdioTee { vfs_id })
+            }
+            Err(err) => {
+                esp_vfs_unregister_with_id(vfs_id);
+                Err(err)
+            }
+        }
+    }
+
+    #[cfg(esp_idf_version_at_least_5_4_0)]
+    unsafe fn register(ops: &Ops, vfs_id: &mut esp_vfs_id_t) -> esp_err_t {
+        // (the table is copied, as it is not registered as static)
+        esp_vfs_register_fs_with_id(ops, ESP_VFS_FLAG_DEFAULT as _, ptr::null_mut(), vfs_id)
+    }
+
+    #[cfg(not(esp_idf_version_at_least_5_4_0))]
+    unsafe fn register(ops: &Ops, vfs_id: &mut esp_vfs_id_t) -> esp_err_t {
+        esp_vfs_register_with_id(ops, ptr::null_mut(), vfs_id)
+    }
+
+    unsafe extern "C" fn write(fd: c_int, data: *const c_void, size: size_t) -> ssize_t {
+        // `fd` is the local descriptor of the entry, i.e. the target descriptor
+        let written = crate::write(fd, data, size);
+
+        if written > 0 {
+            if let Some(sink) = sink(&STDIO_SINK) {
+                let logging = log_task_key().is_some_and(|key| !pthread_getspecific(key).is_null());
+
+                if !logging {
+                    let source = if fd == STDOUT_TARGET.load(Ordering::Acquire) {
+                        StdioSource::Stdout
+                    } else {
+                        StdioSource::Stderr
+                    };
+
+                    sink(
+                        source,
+                        core::slice::from_raw_parts(data as *const u8, written as usize),
+                    );
+                }
+            }
+        }
+
+        written
+    }
+
+    unsafe extern "C" fn close(fd: c_int) -> c_int {
+        crate::close(fd)
+    }
+
+    unsafe extern "C" fn fstat(fd: c_int, st: *mut stat) -> c_int {
+        crate::fstat(fd, st)
+    }
+}
+
+mod vprintf_hook {
+    use core::ffi::{c_char, c_int, c_void};
+    use core::ptr;
+    use core::sync::atomic::Ordering;
+
+    use crate::vprintf::{vsnprintf, VaList, VaListCopy, VprintfLike};
+    use crate::*;
+
+    use super::{log_task_key, sink, StdioSource, LOG_PREVIOUS, LOG_SINK, TEE_LOG_MESSAGE_MAX_LEN};
+
+    pub(super) unsafe extern "C" fn vprintf(format: *const c_char, args: VaList) -> c_int {
+        if let Some(sink) = sink(&LOG_SINK) {
+            let mut buf = [0u8; TEE_LOG_MESSAGE_MAX_LEN + 1];
+
+            // Format a copy, as the arguments are passed on to the previous function below
+            let mut copy = VaListCopy::new(args);
+            let len = vsnprintf(
+                buf.as_mut_ptr() as *mut _,
+                buf.len(),
+                format,
+                copy.as_va_list(),
+            );
+
+            if len > 0 {
+                sink(
+                    StdioSource::Log,
+                    &buf[..(len as usize).min(TEE_LOG_MESSAGE_MAX_LEN)],
+                );
+            }
+        }
+
+        let previous = LOG_PREVIOUS.load(Ordering::Acquire);
+        if previous.is_null() {
+            return 0;
+        }
+
+        let previous = core::mem::transmute::<*mut c_void, VprintfLike>(previous);
+
+        // Mark the output of the previous function - written by the current task -
+        // as already passed to the sink; a nested call leaves the mark to the outer one
+        let marked = log_task_key().filter(|&key| {
+            pthread_getspecific(key).is_null()
+                && pthread_setspecific(key, ptr::NonNull::<c_void>::dangling().as_ptr()) == 0
+        });
+
+        let result = previous(format, args);
+
+        if let Some(key) = marked {
+            pthread_setspecific(key, ptr::null());
+        }
+
+        result
+    }
+}
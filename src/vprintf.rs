@@ -0,0 +1,57 @@
+//! The `va_list` based plumbing needed for hooking the ESP-IDF logging with
+//! `esp_log_set_vprintf`, shared by [`tee_esp_log`](crate::tee_esp_log) and -
+//! with the `log` feature - by `capture_esp_log`.
+
+use core::ffi::{c_char, c_int, c_void};
+
+/// A `va_list` as passed to a function: on RISC-V, `va_list` is a pointer
+/// to the variadic arguments, passed by value; on Xtensa, it is a one-element
+/// array of a 3-word structure, which decays to a pointer to it
+pub(crate) type VaList = *mut c_void;
+
+pub(crate) type VprintfLike = unsafe extern "C" fn(*const c_char, VaList) -> c_int;
+
+extern "C" {
+    // Declared here, as the `v*printf` functions - and the `va_list` based
+    // `vprintf_like_t` - are not part of the bindings
+    #[allow(clashing_extern_declarations)]
+    pub(crate) fn esp_log_set_vprintf(func: Option<VprintfLike>) -> Option<VprintfLike>;
+
+    pub(crate) fn vsnprintf(
+        buf: *mut c_char,
+        size: usize,
+        format: *const c_char,
+        args: VaList,
+    ) -> c_int;
+}
+
+/// A copy of a `va_list`, as made by `va_copy`
+#[cfg(target_arch = "xtensa")]
+pub(crate) struct VaListCopy([usize; 3]);
+
+#[cfg(target_arch = "xtensa")]
+impl VaListCopy {
+    pub(crate) unsafe fn new(args: VaList) -> Self {
+        // The `__va_stk`, `__va_reg` and `__va_ndx` fields of `__va_list_tag`
+        Self(*(args as *const [usize; 3]))
+    }
+
+    pub(crate) fn as_va_list(&mut self) -> VaList {
+        self.0.as_mut_ptr() as _
+    }
+}
+
+/// A copy of a `va_list`, as made by `va_copy`
+#[cfg(not(target_arch = "xtensa"))]
+pub(crate) struct VaListCopy(VaList);
+
+#[cfg(not(target_arch = "xtensa"))]
+impl VaListCopy {
+    pub(crate) unsafe fn new(args: VaList) -> Self {
+        Self(args)
+    }
+
+    pub(crate) fn as_va_list(&mut self) -> VaList {
+        self.0
+    }
+}
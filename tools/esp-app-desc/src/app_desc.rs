@@ -0,0 +1,186 @@
+//! Parsing and validation of the application description.
+
+use std::fmt;
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::elf::{self, u32_at};
+use crate::{image, layout, to_hex};
+
+/// The application description (`esp_app_desc_t`) of a firmware
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppDesc {
+    pub secure_version: u32,
+    pub version: String,
+    pub project_name: String,
+    pub time: String,
+    pub date: String,
+    pub idf_ver: String,
+    pub app_elf_sha256: [u8; 32],
+    /// The `(min, max)` supported eFuse block revisions, in `major * 100 + minor` format;
+    /// only available with ESP-IDF V5.1.7+, V5.2.3+, V5.3.2+ and V5.4+
+    pub efuse_blk_rev_full: Option<(u16, u16)>,
+    /// The MMU page size in log base 2 format; only available with ESP-IDF V5.4+
+    pub mmu_page_size: Option<u8>,
+}
+
+impl AppDesc {
+    /// Parse and validate the application description at the start of `data`.
+    ///
+    /// Which of the version-dependent fields are present is decided based on the
+    /// ESP-IDF version in the `idf_ver` field.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        ensure!(
+            data.len() >= layout::SIZE,
+            "Truncated application description"
+        );
+        ensure!(
+            u32_at(data, layout::MAGIC_WORD_OFFSET)? == layout::MAGIC_WORD,
+            "Bad application description magic word (expected {:#010x})",
+            layout::MAGIC_WORD
+        );
+
+        let string = |name: &str, offset: usize, len: usize| -> Result<String> {
+            let field = &data[offset..offset + len];
+            let end = field
+                .iter()
+                .position(|b| *b == 0)
+                .with_context(|| format!("The {name} field is not NUL-terminated"))?;
+
+            String::from_utf8(field[..end].to_vec())
+                .with_context(|| format!("The {name} field is not valid UTF-8"))
+        };
+
+        let idf_ver = string("idf_ver", layout::IDF_VER_OFFSET, layout::IDF_VER_LEN)?;
+        let (major, minor, patch) = parse_idf_ver(&idf_ver);
+
+        let efuse_blk_rev_full = layout::has_efuse_blk_rev_full(major, minor, patch).then(|| {
+            (
+                u16_at(data, layout::MIN_EFUSE_BLK_REV_FULL_OFFSET),
+                u16_at(data, layout::MAX_EFUSE_BLK_REV_FULL_OFFSET),
+            )
+        });
+
+        let mmu_page_size = layout::has_mmu_page_size(major, minor, patch)
+            .then(|| data[layout::MMU_PAGE_SIZE_OFFSET]);
+
+        let mut time = string("time", layout::TIME_OFFSET, layout::TIME_LEN)?;
+        let mut date = string("date", layout::DATE_OFFSET, layout::DATE_LEN)?;
+
+        // The legacy `esp_app_desc!()` form of the macro swaps the date and time fields
+        if time.contains('-') && date.contains(':') {
+            std::mem::swap(&mut time, &mut date);
+        }
+
+        Ok(Self {
+            secure_version: u32_at(data, layout::SECURE_VERSION_OFFSET)?,
+            version: string("version", layout::VERSION_OFFSET, layout::VERSION_LEN)?,
+            project_name: string(
+                "project_name",
+                layout::PROJECT_NAME_OFFSET,
+                layout::PROJECT_NAME_LEN,
+            )?,
+            time,
+            date,
+            idf_ver,
+            app_elf_sha256: data[layout::APP_ELF_SHA256_OFFSET
+                ..layout::APP_ELF_SHA256_OFFSET + layout::APP_ELF_SHA256_LEN]
+                .try_into()?,
+            efuse_blk_rev_full,
+            mmu_page_size,
+        })
+    }
+
+    /// Read the application description from the `.rodata_desc` section of the ELF file `elf`.
+    pub fn from_elf(elf: &[u8]) -> Result<Self> {
+        let section = elf::section(elf, layout::LINK_SECTION)?;
+        ensure!(
+            section.has_data,
+            "The {} section has no data",
+            layout::LINK_SECTION
+        );
+
+        Self::parse(&elf[section.offset..section.offset + section.size])
+    }
+
+    /// Read the application description from the flash image `image`.
+    ///
+    /// The description is at the start of the segment holding the `.rodata_desc`
+    /// section, which is normally the first one.
+    pub fn from_image(image: &[u8]) -> Result<Self> {
+        for segment in image::segments(image)? {
+            let data = &image[segment.offset..segment.offset + segment.size];
+
+            if u32_at(data, layout::MAGIC_WORD_OFFSET).ok() == Some(layout::MAGIC_WORD) {
+                return Self::parse(data);
+            }
+        }
+
+        bail!("No application description in the flash image")
+    }
+
+    /// Read the application description from `data`, which is either an ELF file or a flash image.
+    pub fn from_firmware(data: &[u8]) -> Result<Self> {
+        if image::is_image(data) {
+            Self::from_image(data)
+        } else {
+            Self::from_elf(data)
+        }
+    }
+}
+
+impl fmt::Display for AppDesc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Project name:     {}", self.project_name)?;
+        writeln!(f, "App version:      {}", self.version)?;
+        writeln!(f, "Secure version:   {}", self.secure_version)?;
+        writeln!(f, "Compile time:     {} {}", self.date, self.time)?;
+        writeln!(f, "ESP-IDF:          {}", self.idf_ver)?;
+        writeln!(f, "ELF file SHA256:  {}", to_hex(&self.app_elf_sha256))?;
+
+        if let Some((min, max)) = self.efuse_blk_rev_full {
+            writeln!(
+                f,
+                "eFuse block rev:  v{}.{} - v{}.{}",
+                min / 100,
+                min % 100,
+                max / 100,
+                max % 100
+            )?;
+        }
+
+        if let Some(mmu_page_size) = self.mmu_page_size {
+            writeln!(f, "MMU page size:    {}", mmu_page_size_str(mmu_page_size))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn mmu_page_size_str(log2: u8) -> String {
+    match log2 {
+        0 => "unspecified".into(),
+        log2 if log2 >= 20 => format!("{}MB", 1u32 << (log2 - 20)),
+        log2 if log2 >= 10 => format!("{}KB", 1u32 << (log2 - 10)),
+        log2 => format!("{}B", 1u32 << log2),
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// Parse the `major.minor.patch` ESP-IDF version out of an `idf_ver` field like `5.3.2`
+/// or `v5.3.2-dirty`; missing components are reported as 0.
+fn parse_idf_ver(idf_ver: &str) -> (u32, u32, u32) {
+    let mut parts = idf_ver
+        .trim_start_matches('v')
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|part| part.parse().unwrap_or(0));
+
+    (
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+    )
+}
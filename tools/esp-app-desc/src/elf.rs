@@ -0,0 +1,104 @@
+//! A minimal reader of the section headers of 32-bit little-endian ELF files,
+//! which is what all Xtensa and RISC-V ESP targets produce.
+
+use anyhow::{bail, ensure, Context, Result};
+
+const SHT_NOBITS: u32 = 8;
+
+/// A section of an ELF file
+#[derive(Clone, Debug)]
+pub struct Section {
+    /// The name of the section, e.g. `.rodata_desc`
+    pub name: String,
+    /// The virtual address of the section
+    pub addr: u32,
+    /// The offset of the section data in the file
+    pub offset: usize,
+    /// The size of the section
+    pub size: usize,
+    /// Whether the section has data in the file (i.e. is not `.bss`-like)
+    pub has_data: bool,
+}
+
+/// Parse the section headers of the ELF file `data`.
+pub fn sections(data: &[u8]) -> Result<Vec<Section>> {
+    ensure!(data.len() >= 52, "Not an ELF file: too short");
+    ensure!(&data[..4] == b"\x7fELF", "Not an ELF file: bad magic");
+    ensure!(data[4] == 1, "Not a 32-bit ELF file");
+    ensure!(data[5] == 1, "Not a little-endian ELF file");
+
+    let shoff = u32_at(data, 0x20)? as usize;
+    let shentsize = u16_at(data, 0x2e)? as usize;
+    let shnum = u16_at(data, 0x30)? as usize;
+    let shstrndx = u16_at(data, 0x32)? as usize;
+
+    ensure!(
+        shentsize >= 40,
+        "Invalid ELF section header size {shentsize}"
+    );
+
+    let header = |index: usize| -> Result<(u32, u32, u32, usize, usize)> {
+        let base = shoff + index * shentsize;
+
+        Ok((
+            u32_at(data, base)?,
+            u32_at(data, base + 4)?,
+            u32_at(data, base + 12)?,
+            u32_at(data, base + 16)? as usize,
+            u32_at(data, base + 20)? as usize,
+        ))
+    };
+
+    ensure!(shstrndx < shnum, "Invalid ELF section name table index");
+    let (_, _, _, strtab_offset, strtab_size) = header(shstrndx)?;
+    let strtab = data
+        .get(strtab_offset..strtab_offset + strtab_size)
+        .context("ELF section name table out of bounds")?;
+
+    (0..shnum)
+        .map(|index| {
+            let (name, kind, addr, offset, size) = header(index)?;
+
+            let name = strtab
+                .get(name as usize..)
+                .and_then(|s| s.split(|b| *b == 0).next())
+                .context("ELF section name out of bounds")?;
+
+            let has_data = kind != SHT_NOBITS;
+            if has_data && data.get(offset..offset + size).is_none() {
+                bail!(
+                    "ELF section {} out of bounds",
+                    String::from_utf8_lossy(name)
+                );
+            }
+
+            Ok(Section {
+                name: String::from_utf8_lossy(name).into_owned(),
+                addr,
+                offset,
+                size,
+                has_data,
+            })
+        })
+        .collect()
+}
+
+/// Find the section named `name` in the ELF file `data`.
+pub fn section(data: &[u8], name: &str) -> Result<Section> {
+    sections(data)?
+        .into_iter()
+        .find(|section| section.name == name)
+        .with_context(|| format!("No {name} section in the ELF file"))
+}
+
+pub(crate) fn u16_at(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .context("Unexpected end of file")
+}
+
+pub(crate) fn u32_at(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .context("Unexpected end of file")
+}
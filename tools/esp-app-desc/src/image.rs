@@ -0,0 +1,65 @@
+//! A minimal reader of the segments of ESP-IDF application flash images (`.bin` files
+//! as produced by `esptool elf2image` or `espflash save-image`).
+
+use anyhow::{ensure, Context, Result};
+
+use crate::elf::u32_at;
+
+/// The magic byte at the start of every ESP flash image
+pub const MAGIC: u8 = 0xe9;
+
+/// The size of the image header, including the extended header
+const HEADER_SIZE: usize = 24;
+/// The size of a segment header
+const SEGMENT_HEADER_SIZE: usize = 8;
+
+/// A segment of a flash image
+#[derive(Clone, Debug)]
+pub struct Segment {
+    /// The load address of the segment
+    pub addr: u32,
+    /// The offset of the segment data in the image
+    pub offset: usize,
+    /// The size of the segment data
+    pub size: usize,
+}
+
+/// Return `true` if `data` looks like a flash image.
+pub fn is_image(data: &[u8]) -> bool {
+    data.first() == Some(&MAGIC)
+}
+
+/// Parse the segment headers of the flash image `data`.
+pub fn segments(data: &[u8]) -> Result<Vec<Segment>> {
+    ensure!(data.len() >= HEADER_SIZE, "Not a flash image: too short");
+    ensure!(is_image(data), "Not a flash image: bad magic");
+
+    let count = data[1] as usize;
+
+    let mut offset = HEADER_SIZE;
+
+    (0..count)
+        .map(|index| {
+            let addr = u32_at(data, offset)
+                .with_context(|| format!("Flash image segment {index} out of bounds"))?;
+            let size = u32_at(data, offset + 4)
+                .with_context(|| format!("Flash image segment {index} out of bounds"))?
+                as usize;
+
+            let segment = Segment {
+                addr,
+                offset: offset + SEGMENT_HEADER_SIZE,
+                size,
+            };
+
+            ensure!(
+                data.len() >= segment.offset + size,
+                "Flash image segment {index} out of bounds"
+            );
+
+            offset = segment.offset + size;
+
+            Ok(segment)
+        })
+        .collect()
+}
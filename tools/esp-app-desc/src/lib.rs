@@ -0,0 +1,71 @@
+//! Host-side tooling for the ESP-IDF application description (`esp_app_desc_t`) which
+//! the `esp_idf_sys::esp_app_desc!` macro places in the `.rodata_desc` section of the firmware.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{ensure, Result};
+use sha2::{Digest, Sha256};
+
+pub use app_desc::AppDesc;
+
+mod app_desc;
+pub mod elf;
+pub mod image;
+
+#[path = "../../../src/app_desc_layout.rs"]
+pub mod layout;
+
+/// Compute the SHA256 of the ELF file `elf` and embed it in the `app_elf_sha256` field of
+/// its application description, returning the hash.
+///
+/// The hash is computed with the `app_elf_sha256` field zeroed - i.e. over the ELF file as
+/// produced by the linker - which is the value `esptool elf2image --elf-sha256-offset` embeds
+/// in the flash image of a C project, and which `esp_app_get_elf_sha256` reports. Embedding
+/// is therefore idempotent.
+pub fn embed_elf_sha256(elf: &mut [u8]) -> Result<[u8; 32]> {
+    let offset = app_desc_offset(elf)? + layout::APP_ELF_SHA256_OFFSET;
+    let field = offset..offset + layout::APP_ELF_SHA256_LEN;
+
+    elf[field.clone()].fill(0);
+
+    let sha256: [u8; 32] = Sha256::digest(&*elf).into();
+
+    elf[field].copy_from_slice(&sha256);
+
+    Ok(sha256)
+}
+
+/// Like [`embed_elf_sha256`], but patching the ELF file at `path` in place.
+pub fn embed_elf_sha256_file(path: impl AsRef<Path>) -> Result<[u8; 32]> {
+    let path = path.as_ref();
+
+    let mut elf = fs::read(path)?;
+    let sha256 = embed_elf_sha256(&mut elf)?;
+    fs::write(path, elf)?;
+
+    Ok(sha256)
+}
+
+/// Get the file offset of the application description in the ELF file `elf`.
+fn app_desc_offset(elf: &[u8]) -> Result<usize> {
+    let section = elf::section(elf, layout::LINK_SECTION)?;
+
+    ensure!(
+        section.has_data && section.size >= layout::SIZE,
+        "The {} section is too small for an application description",
+        layout::LINK_SECTION
+    );
+    ensure!(
+        elf::u32_at(elf, section.offset + layout::MAGIC_WORD_OFFSET)? == layout::MAGIC_WORD,
+        "No application description (bad magic word) at the start of the {} section",
+        layout::LINK_SECTION
+    );
+
+    Ok(section.offset)
+}
+
+/// Format `bytes` as a lowercase hex string.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,45 @@
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+use anyhow::{bail, Result};
+
+const USAGE: &str = "\
+Usage: esp-app-desc <COMMAND> <FILE>
+
+Commands:
+  show <FILE>           Validate and print the application description of an ELF file or a flash image
+  embed-sha256 <ELF>    Embed the SHA256 of the ELF file in its application description";
+
+fn main() -> ExitCode {
+    match run(env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<()> {
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["show", file] => {
+            print!(
+                "{}",
+                esp_app_desc::AppDesc::from_firmware(&fs::read(file)?)?
+            );
+        }
+        ["embed-sha256", elf] => {
+            let sha256 = esp_app_desc::embed_elf_sha256_file(elf)?;
+            println!("{}", esp_app_desc::to_hex(&sha256));
+        }
+        _ => bail!("Invalid arguments\n\n{USAGE}"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+#![allow(dead_code)]
+
+/// Build a minimal 32-bit little-endian ELF file with the given `(name, addr, data)` sections.
+pub fn elf(sections: &[(&str, u32, &[u8])]) -> Vec<u8> {
+    let mut strtab = vec![0u8];
+    let mut names = Vec::new();
+    for (name, _, _) in sections.iter().chain([&(".shstrtab", 0, &[][..])]) {
+        names.push(strtab.len() as u32);
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+    }
+
+    let mut data = vec![0u8; 52];
+    let mut headers = vec![[0u32; 10]];
+
+    for (index, (_, addr, content)) in sections.iter().enumerate() {
+        headers.push([
+            names[index],
+            1,
+            2,
+            *addr,
+            data.len() as u32,
+            content.len() as u32,
+            0,
+            0,
+            4,
+            0,
+        ]);
+        data.extend_from_slice(content);
+    }
+
+    headers.push([
+        names[sections.len()],
+        3,
+        0,
+        0,
+        data.len() as u32,
+        strtab.len() as u32,
+        0,
+        0,
+        1,
+        0,
+    ]);
+    data.extend_from_slice(&strtab);
+
+    data.resize(data.len().next_multiple_of(4), 0);
+
+    let shoff = data.len() as u32;
+    for header in &headers {
+        for word in header {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    data[..4].copy_from_slice(b"\x7fELF");
+    data[4] = 1; // ELFCLASS32
+    data[5] = 1; // ELFDATA2LSB
+    data[6] = 1; // EV_CURRENT
+    data[0x10..0x12].copy_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    data[0x12..0x14].copy_from_slice(&0xf3u16.to_le_bytes()); // EM_RISCV
+    data[0x20..0x24].copy_from_slice(&shoff.to_le_bytes());
+    data[0x28..0x2a].copy_from_slice(&52u16.to_le_bytes());
+    data[0x2e..0x30].copy_from_slice(&40u16.to_le_bytes());
+    data[0x30..0x32].copy_from_slice(&(headers.len() as u16).to_le_bytes());
+    data[0x32..0x34].copy_from_slice(&(headers.len() as u16 - 1).to_le_bytes());
+
+    data
+}
+
+/// Build an application description with the given version and project name.
+pub fn app_desc(version: &str, project_name: &str) -> Vec<u8> {
+    let mut desc = vec![0u8; 256];
+
+    desc[..4].copy_from_slice(&0xABCD5432u32.to_le_bytes());
+    desc[16..16 + version.len()].copy_from_slice(version.as_bytes());
+    desc[48..48 + project_name.len()].copy_from_slice(project_name.as_bytes());
+
+    desc
+}
+
+/// Build a minimal flash image with the given `(addr, data)` segments.
+pub fn image(segments: &[(u32, &[u8])]) -> Vec<u8> {
+    let mut image = vec![0u8; 24];
+
+    image[0] = 0xe9;
+    image[1] = segments.len() as u8;
+
+    for (addr, data) in segments {
+        image.extend_from_slice(&addr.to_le_bytes());
+        image.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        image.extend_from_slice(data);
+    }
+
+    image
+}
+
+/// Set the string field at `offset` of the application description `desc` to `value`.
+pub fn set(desc: &mut [u8], offset: usize, value: &str) {
+    desc[offset..offset + value.len()].copy_from_slice(value.as_bytes());
+}
@@ -0,0 +1,64 @@
+use esp_app_desc::{elf, embed_elf_sha256, layout};
+use sha2::{Digest, Sha256};
+
+mod common;
+
+fn sample() -> Vec<u8> {
+    common::elf(&[
+        (".iram0.text", 0x4037_0000, &[0x13; 64]),
+        (
+            layout::LINK_SECTION,
+            0x3c00_0020,
+            &common::app_desc("1.2.3", "sample"),
+        ),
+        (".flash.rodata", 0x3c00_0120, b"Hello, world!"),
+    ])
+}
+
+fn sha256_field(elf: &[u8]) -> [u8; 32] {
+    let section = elf::section(elf, layout::LINK_SECTION).unwrap();
+    let offset = section.offset + layout::APP_ELF_SHA256_OFFSET;
+
+    elf[offset..offset + layout::APP_ELF_SHA256_LEN]
+        .try_into()
+        .unwrap()
+}
+
+#[test]
+fn embeds_the_sha256_of_the_linked_elf() {
+    let original = sample();
+    let expected: [u8; 32] = Sha256::digest(&original).into();
+
+    let mut elf = original.clone();
+    let sha256 = embed_elf_sha256(&mut elf).unwrap();
+
+    assert_eq!(sha256, expected);
+    assert_eq!(sha256_field(&elf), expected);
+    assert_eq!(elf.len(), original.len());
+}
+
+#[test]
+fn is_idempotent() {
+    let mut elf = sample();
+
+    let first = embed_elf_sha256(&mut elf).unwrap();
+    let patched = elf.clone();
+    let second = embed_elf_sha256(&mut elf).unwrap();
+
+    assert_eq!(first, second);
+    assert_eq!(elf, patched);
+}
+
+#[test]
+fn rejects_elf_without_app_desc() {
+    let mut elf = common::elf(&[(".iram0.text", 0x4037_0000, &[0x13; 64])]);
+    assert!(embed_elf_sha256(&mut elf).is_err());
+
+    let mut elf = common::elf(&[(layout::LINK_SECTION, 0x3c00_0020, &[0; 256])]);
+    assert!(embed_elf_sha256(&mut elf).is_err());
+}
+
+#[test]
+fn rejects_non_elf() {
+    assert!(embed_elf_sha256(&mut b"not an ELF file at all, just some text".to_vec()).is_err());
+}
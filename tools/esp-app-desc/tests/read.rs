@@ -0,0 +1,89 @@
+use esp_app_desc::{layout, AppDesc};
+
+mod common;
+
+fn desc(idf_ver: &str) -> Vec<u8> {
+    let mut desc = common::app_desc("1.2.3", "blinky");
+
+    common::set(&mut desc, layout::TIME_OFFSET, "12:34:56");
+    common::set(&mut desc, layout::DATE_OFFSET, "2026-01-02");
+    common::set(&mut desc, layout::IDF_VER_OFFSET, idf_ver);
+
+    desc[layout::MIN_EFUSE_BLK_REV_FULL_OFFSET..][..2].copy_from_slice(&0u16.to_le_bytes());
+    desc[layout::MAX_EFUSE_BLK_REV_FULL_OFFSET..][..2].copy_from_slice(&199u16.to_le_bytes());
+    desc[layout::MMU_PAGE_SIZE_OFFSET] = 16;
+
+    desc
+}
+
+#[test]
+fn reads_elf() {
+    let elf = common::elf(&[
+        (".text", 0x4200_0000, &[0; 16]),
+        (".rodata_desc", 0x3c00_0020, &desc("v5.1.2")),
+    ]);
+
+    let desc = AppDesc::from_firmware(&elf).unwrap();
+
+    assert_eq!(desc.version, "1.2.3");
+    assert_eq!(desc.project_name, "blinky");
+    assert_eq!(desc.time, "12:34:56");
+    assert_eq!(desc.date, "2026-01-02");
+    assert_eq!(desc.idf_ver, "v5.1.2");
+    assert_eq!(desc.efuse_blk_rev_full, None);
+    assert_eq!(desc.mmu_page_size, None);
+}
+
+#[test]
+fn reads_image() {
+    let image = common::image(&[(0x3c00_0020, &desc("v5.4.1")), (0x4080_0000, &[0; 32])]);
+
+    let desc = AppDesc::from_firmware(&image).unwrap();
+
+    assert_eq!(desc.project_name, "blinky");
+    assert_eq!(desc.efuse_blk_rev_full, Some((0, 199)));
+    assert_eq!(desc.mmu_page_size, Some(16));
+    assert!(desc.to_string().contains("MMU page size:    64KB"));
+}
+
+#[test]
+fn version_dependent_fields() {
+    let parse = |idf_ver| AppDesc::parse(&desc(idf_ver)).unwrap();
+
+    assert_eq!(parse("v5.1.6").efuse_blk_rev_full, None);
+    assert_eq!(parse("v5.1.7").efuse_blk_rev_full, Some((0, 199)));
+    assert_eq!(parse("v5.3.2-dirty").efuse_blk_rev_full, Some((0, 199)));
+    assert_eq!(parse("v5.3.2-dirty").mmu_page_size, None);
+    assert_eq!(parse("v6.0").mmu_page_size, Some(16));
+}
+
+#[test]
+fn fixes_up_legacy_date_and_time() {
+    let mut legacy = desc("v5.1.2");
+
+    legacy[layout::TIME_OFFSET..][..layout::TIME_LEN].fill(0);
+    legacy[layout::DATE_OFFSET..][..layout::DATE_LEN].fill(0);
+    common::set(&mut legacy, layout::TIME_OFFSET, "2026-01-02");
+    common::set(&mut legacy, layout::DATE_OFFSET, "12:34:56");
+
+    let desc = AppDesc::parse(&legacy).unwrap();
+
+    assert_eq!(desc.time, "12:34:56");
+    assert_eq!(desc.date, "2026-01-02");
+}
+
+#[test]
+fn rejects_invalid_descriptions() {
+    let mut bad_magic = desc("v5.1.2");
+    bad_magic[0] = 0;
+    assert!(AppDesc::parse(&bad_magic).is_err());
+
+    let mut unterminated = desc("v5.1.2");
+    unterminated[layout::VERSION_OFFSET..][..layout::VERSION_LEN].fill(b'x');
+    assert!(AppDesc::parse(&unterminated).is_err());
+
+    assert!(AppDesc::parse(&desc("v5.1.2")[..100]).is_err());
+
+    let image = common::image(&[(0x4080_0000, &[0; 32])]);
+    assert!(AppDesc::from_firmware(&image).is_err());
+}